@@ -1,5 +1,8 @@
 use crate::core::env::{CabinetEnv, Env};
-use crate::core::{Engine, Error, Output, OutputObject, Plan, ServiceEntity};
+use crate::core::{
+    Budget, CallMode, Engine, Error, Output, OutputObject, Plan, ServiceEntity, Usage, UsageLedger,
+};
+use crate::utils::path;
 use serde_json::Value;
 use std::any::Any;
 use std::collections::HashMap;
@@ -9,6 +12,7 @@ use std::ops::DerefMut;
 use std::sync::Arc;
 use std::task::Waker;
 use wd_tools::sync::Am;
+use wd_tools::PFErr;
 
 #[derive(Default, Copy, Clone)]
 pub enum CtxStatus {
@@ -81,6 +85,7 @@ pub struct Ctx {
     pub ce: Arc<Am<Metadata>>,
     pub plan: Arc<Am<Box<dyn Plan + Sync + 'static>>>,
     pub env: Arc<dyn Env + 'static>,
+    pub usage: Arc<Am<UsageLedger>>,
     pub rt: Engine,
 }
 impl Clone for Ctx {
@@ -90,6 +95,7 @@ impl Clone for Ctx {
             plan: self.plan.clone(),
             ce: self.ce.clone(),
             env: self.env.clone(),
+            usage: self.usage.clone(),
         }
     }
 }
@@ -111,6 +117,16 @@ impl Ctx {
             }
         }
     }
+    //在当前ctx中单独调用一个service：经过全部中间件，但不推进plan
+    pub async fn sub_call(&self, mut se: ServiceEntity, keep_var: bool) -> anyhow::Result<Output> {
+        match self.rt.load_service(se.service_name.as_str()).await {
+            Some(s) => se = se.set_service(s),
+            None => return Err(Error::ServiceNotFound(se.service_name).into()),
+        }
+        se.middle_index = 0;
+        se.call_mode = CallMode::Detached { keep_var };
+        self.clone().next(se).await
+    }
     pub fn new<P: Plan + Sync + 'static>(rt: Engine, plan: P) -> Self {
         let ctx = Metadata {
             error: None,
@@ -119,8 +135,10 @@ impl Ctx {
             waker: None,
             vars: Default::default(),
         };
+        let usage = UsageLedger::new(rt.entity.budget);
         Self {
             rt,
+            usage: Arc::new(Am::new(usage)),
             plan: Arc::new(Am::new(Box::new(plan))),
             env: Arc::new(CabinetEnv::new()),
             ce: Arc::new(Am::new(ctx)),
@@ -178,6 +196,20 @@ impl Ctx {
         })
         .await
     }
+    //路径不匹配时返回具体原因
+    pub async fn try_get_var_field(&self, node: &str, field: &str) -> anyhow::Result<Value> {
+        self.async_mut_metadata(|c| {
+            let res = match c.vars.get(node) {
+                Some(val) => match val.get_val(field) {
+                    Some(v) => Ok(v),
+                    None => path::query(&val.as_val(), field),
+                },
+                None => anyhow::anyhow!("not found node[{}]", node).err(),
+            };
+            async move { res }
+        })
+        .await
+    }
     pub async fn get_var(&self, node: &str) -> Value {
         self.async_mut_metadata(|c| {
             let res = if let Some(val) = c.vars.get(node) {
@@ -189,6 +221,22 @@ impl Ctx {
         })
             .await
     }
+    //同步读取变量，用于plan.next等非异步的场景
+    pub fn deref_var_field(&self, node: &str, field: &str) -> Option<Value> {
+        self.deref_mut_metadata(|c| c.vars.get(node).and_then(|x| x.get_val(field)))
+    }
+    pub fn deref_var(&self, node: &str) -> Value {
+        self.deref_mut_metadata(|c| c.vars.get(node).map(|x| x.as_val()))
+            .unwrap_or(Value::Null)
+    }
+    pub fn deref_var_path(&self, path: &str) -> Option<Value> {
+        let (node, field) = path::split_root(path);
+        if !field.is_empty() {
+            self.deref_var_field(node, field)
+        } else {
+            self.deref_mut_metadata(|c| c.vars.get(node).map(|x| x.as_val()))
+        }
+    }
     pub async fn update_var<T:'static,Out>(&self,var_name: &str,handle:impl FnOnce(Option<&mut T>) -> Out) -> Out {
         self.async_mut_metadata(|m| {
             let out = if let Some(val) = m.vars.get_mut(var_name) {
@@ -256,7 +304,9 @@ impl Ctx {
         c
     }
     pub fn fork<P: Plan + Sync + 'static>(&self, p: P) -> Self {
-        let ctx = Self::new(self.rt.clone(), p).set_env(self.env.clone());
+        let mut ctx = Self::new(self.rt.clone(), p).set_env(self.env.clone());
+        //子流程的用量计入同一次运行
+        ctx.usage = self.usage.clone();
         ctx
     }
     // pub(crate) fn set_waker(self, waker: Waker) -> Self {
@@ -270,6 +320,25 @@ impl Ctx {
     pub fn get_env(&self) -> Arc<dyn Env + 'static> {
         self.env.clone()
     }
+    pub fn set_budget(self, budget: Budget) -> Self {
+        self.usage.synchronize().budget = budget;
+        self
+    }
+    //记录节点用量，超出预算时返回Error::BudgetExceeded
+    pub fn record_usage(&self, node: &str, usage: Usage) -> anyhow::Result<()> {
+        self.usage.synchronize().record(node, &usage)?;
+        Ok(())
+    }
+    pub fn check_budget(&self, node: &str) -> anyhow::Result<()> {
+        self.usage.synchronize().check(node)?;
+        Ok(())
+    }
+    pub fn usage(&self) -> Usage {
+        self.usage.synchronize().total.clone()
+    }
+    pub fn usage_by_node(&self) -> HashMap<String, Usage> {
+        self.usage.synchronize().nodes.clone()
+    }
 
     pub async fn set_any_error(&self, err: anyhow::Error) {
         let err = err
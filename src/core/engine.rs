@@ -1,8 +1,11 @@
 use crate::core::hook::FlowCallback;
 use crate::core::service::{MapServiceLoader, Service, ServiceLoader};
-use crate::core::{Ctx, CtxStatus, Error, Plan, RuntimePool, ServiceEntity, TokioRuntimePool};
+use crate::core::{
+    Budget, Ctx, CtxStatus, Error, Plan, RuntimePool, ServiceEntity, TokioRuntimePool,
+};
 use pin_project_lite::pin_project;
 use std::any::Any;
+use std::collections::HashMap;
 use std::future::Future;
 use std::ops::{Deref, DerefMut};
 use std::pin::Pin;
@@ -16,8 +19,14 @@ pub struct EngineRT {
     pub runtime_pool: Box<dyn RuntimePool + Sync + 'static>,
     pub flow_start_callback: Vec<Box<dyn FlowCallback + Sync + 'static>>,
     pub flow_end_callback: Vec<Box<dyn FlowCallback + Sync + 'static>>,
+    pub plans: HashMap<String, PlanFactory>,
+    //每次运行的默认预算，可通过Ctx::set_budget覆盖
+    pub budget: Budget,
 }
 
+//注册的plan每次加载都会得到一份新的拷贝
+pub type PlanFactory = Arc<dyn Fn() -> Box<dyn Plan + Sync + 'static> + Send + Sync>;
+
 impl Default for EngineRT {
     fn default() -> Self {
         let service_loader = Box::new(MapServiceLoader::default());
@@ -31,6 +40,8 @@ impl Default for EngineRT {
             runtime_pool,
             flow_start_callback,
             flow_end_callback,
+            plans: HashMap::new(),
+            budget: Budget::default(),
         }
         .append_service_middle(Engine::base_hook)
     }
@@ -57,6 +68,19 @@ impl EngineRT {
         self.flow_end_callback.push(Box::new(callback));
         self
     }
+    pub fn register_plan<N: Into<String>, P: Plan + Clone + Sync + 'static>(
+        mut self,
+        name: N,
+        plan: P,
+    ) -> Self {
+        self.plans
+            .insert(name.into(), Arc::new(move || Box::new(plan.clone())));
+        self
+    }
+    pub fn set_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
     pub fn build(self) -> Engine {
         Engine {
             entity: Arc::new(self),
@@ -182,6 +206,17 @@ impl Engine {
     pub async fn load_service(&self, name: &str) -> Option<Arc<dyn Service + Sync + 'static>> {
         self.entity.service_loader.load(name).await
     }
+    pub fn service_names(&self) -> Vec<String> {
+        self.entity.service_loader.names()
+    }
+    pub fn load_plan(&self, name: &str) -> Option<Box<dyn Plan + Sync + 'static>> {
+        self.entity.plans.get(name).map(|f| f())
+    }
+    pub fn plan_names(&self) -> Vec<String> {
+        let mut list = self.entity.plans.keys().cloned().collect::<Vec<_>>();
+        list.sort();
+        list
+    }
     pub fn go<In: Any + Send>(ctx: Ctx, input: In) {
         tokio::spawn(async move {
             if let Err(err) = Self::raw_run(ctx.clone(), input).await {
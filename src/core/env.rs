@@ -1,11 +1,16 @@
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
+use std::sync::Arc;
 use wd_tools::sync::Am;
 
 #[async_trait::async_trait]
 pub trait Env: Send + Sync {
     async fn watch(&self, t: TypeId) -> anyhow::Result<Option<Box<dyn Any>>>;
     async fn feedback(&self, info: Box<dyn Any + Send>) -> anyhow::Result<()>;
+    //只读的共享资源(配置、客户端等)，读取后不会被移除
+    fn resource(&self, _t: TypeId) -> Option<Arc<dyn Any + Send + Sync>> {
+        None
+    }
 }
 
 #[async_trait::async_trait]
@@ -14,33 +19,54 @@ pub trait EnvExt: Send + Sync {
     async fn watch_ext<T: Any>(&self) -> anyhow::Result<Option<T>>;
     #[allow(unused)]
     async fn feedback_ext<T: Any + Send>(&self, info: T) -> anyhow::Result<()>;
+    fn resource_ext<T: Any + Send + Sync>(&self) -> Option<Arc<T>>;
 }
 
 #[async_trait::async_trait]
 impl<T: Env + ?Sized + 'static> EnvExt for T {
     async fn watch_ext<A: Any>(&self) -> anyhow::Result<Option<A>> {
-        if let Some(s) = self.watch(TypeId::of::<T>()).await? {
-            let a = s.downcast::<A>().unwrap();
-            Ok(Some(*a))
-        } else {
-            Ok(None)
+        match self.watch(TypeId::of::<A>()).await? {
+            Some(s) => match s.downcast::<A>() {
+                Ok(a) => Ok(Some(*a)),
+                Err(_) => Err(anyhow::anyhow!(
+                    "env watch type[{}] mismatch",
+                    std::any::type_name::<A>()
+                )),
+            },
+            None => Ok(None),
         }
     }
 
     async fn feedback_ext<A: Any + Send>(&self, info: A) -> anyhow::Result<()> {
         self.feedback(Box::new(info)).await
     }
+
+    fn resource_ext<A: Any + Send + Sync>(&self) -> Option<Arc<A>> {
+        self.resource(TypeId::of::<A>())
+            .and_then(|x| x.downcast::<A>().ok())
+    }
 }
 
 pub struct CabinetEnv {
     pub cabinet: Am<HashMap<TypeId, Box<dyn Any>>>,
+    pub resources: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+impl Default for CabinetEnv {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl CabinetEnv {
     pub fn new() -> Self {
         Self {
             cabinet: Am::new(HashMap::new()),
+            resources: HashMap::new(),
         }
     }
+    pub fn set_resource<T: Any + Send + Sync>(mut self, res: T) -> Self {
+        self.resources.insert(TypeId::of::<T>(), Arc::new(res));
+        self
+    }
 }
 #[async_trait::async_trait]
 impl Env for CabinetEnv {
@@ -54,9 +80,14 @@ impl Env for CabinetEnv {
     }
 
     async fn feedback(&self, info: Box<dyn Any + Send>) -> anyhow::Result<()> {
-        let key = (&info).type_id();
+        //按内部值的类型存放，而不是Box的类型
+        let key = (*info).type_id();
         let mut lock = self.cabinet.lock().await;
-        lock.insert(key, Box::new(info));
+        lock.insert(key, info);
         Ok(())
     }
+
+    fn resource(&self, t: TypeId) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.resources.get(&t).cloned()
+    }
 }
@@ -8,6 +8,7 @@ pub enum Error {
     NodeEntityNotFound(String),
     NextNodeNull,
     AnyhowError(anyhow::Error),
+    BudgetExceeded { node: String, reason: String },
 }
 
 impl<T> Into<anyhow::Result<T>> for Error {
@@ -40,6 +41,9 @@ impl Display for Error {
             Error::AnyhowError(e) => {
                 write!(f, "{:?}", e)
             }
+            Error::BudgetExceeded { node, reason } => {
+                write!(f, "BudgetExceeded at node[{}]: {}", node, reason)
+            }
         }
     }
 }
@@ -1,5 +1,7 @@
-use crate::core::{Ctx, Engine, Error, NextPlan, Output, ServiceEntity};
+use crate::core::{CallMode, Ctx, Engine, Error, JsonInput, NextPlan, Output, ServiceEntity};
+use serde_json::{Map, Value};
 use std::future::Future;
+use wd_tools::PFErr;
 
 #[async_trait::async_trait]
 pub trait FlowCallback: Send {
@@ -18,11 +20,29 @@ where
 }
 
 impl Engine {
-    pub async fn base_hook(ctx: Ctx, se: ServiceEntity) -> anyhow::Result<Output> {
+    pub async fn base_hook(ctx: Ctx, mut se: ServiceEntity) -> anyhow::Result<Output> {
         let node = se.node_name.clone();
+        //预算已经用完时不再执行新的节点
+        ctx.check_budget(node.as_str())?;
         let rt = ctx.rt.clone();
+        let output_rule = se.output_rule.take();
+        let call_mode = se.call_mode;
         //处理返回结果
-        let out = ctx.clone().next(se).await?;
+        let mut out = ctx.clone().next(se).await?;
+        if let Some(rule) = output_rule {
+            out = Self::map_output(ctx.clone(), node.as_str(), rule, out).await?;
+        }
+        if let CallMode::Detached { keep_var } = call_mode {
+            if keep_var {
+                let val = Output::new(out.as_val());
+                ctx.async_mut_metadata(|c| {
+                    c.vars.insert(node, val);
+                    async {}
+                })
+                .await;
+            }
+            return Ok(out);
+        }
         let node_key = node.clone();
         ctx.clone()
             .async_mut_metadata(|c| {
@@ -57,4 +77,59 @@ impl Engine {
         }
         Ok(Output::default())
     }
+    //output_rule中的引用路径相对于节点的原始输出
+    async fn map_output(
+        ctx: Ctx,
+        node: &str,
+        rule: JsonInput,
+        out: Output,
+    ) -> anyhow::Result<Output> {
+        let mut val = Value::Object(Map::new());
+        if let Err(e) = rule.transform(ctx, &mut val, Some(out.as_val())).await {
+            return anyhow::anyhow!("Node[{}] output transform error:{e}", node).err();
+        }
+        Ok(Output::new(val))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{CtxSerdeExt, EngineRT, JsonInput, ServiceEntityJson};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::{Obj, ServiceLoaderWrap};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_output_rule() {
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("chat", |_ctx, input: Obj, _se| async move {
+                        let input = Value::from(input);
+                        Ok(json!({
+                            "id": "x1",
+                            "choices": [{"message": {"content": format!("hi {}", input["name"].as_str().unwrap_or(""))}}],
+                        }))
+                    })
+                    .register_json_ext_service("echo", |_ctx, input: Obj, _se| async move {
+                        Ok(Value::from(input))
+                    }),
+            )
+            .build();
+
+        let chat = ServiceEntityJson::default()
+            .set_service_name("chat")
+            .set_output_rule(
+                JsonInput::default()
+                    .add_transform_quote("answer", "choices[0].message.content")
+                    .set_default_json(json!({"source": "${{ id | upper }}"})),
+            );
+        let plan = Pipeline::default()
+            .step(chat)
+            .step(("echo", JsonInput::default()))
+            .check()
+            .unwrap();
+        let res: Value = rt.ctx(plan).serde_run(json!({"name":"art"})).await.unwrap();
+        assert_eq!(res, json!({"answer":"hi art","source":"X1"}));
+    }
 }
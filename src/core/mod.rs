@@ -8,16 +8,21 @@ mod node;
 mod output;
 mod plan;
 mod pool;
+mod schema;
 mod service;
 mod service_json_ext;
+mod usage;
 
 pub use context::*;
 pub use engine::*;
 pub use engine_serde_ext::*;
+pub use env::*;
 pub use error::*;
 pub use node::*;
 pub use output::*;
 pub use plan::*;
 pub use pool::*;
+pub use schema::*;
 pub use service::*;
 pub use service_json_ext::*;
+pub use usage::*;
@@ -11,6 +11,20 @@ pub struct ServiceEntity {
     pub service_name: String,
     pub node_name: String,
     pub config: Box<dyn Any + Send + Sync + 'static>,
+    //以节点原始输出为数据源重新组织输出，再写入vars
+    pub output_rule: Option<JsonInput>,
+    pub call_mode: CallMode,
+}
+
+//Flow:执行完成后按plan继续向下执行
+//Detached:只经过中间件执行自身并返回输出，不推进plan，keep_var表示是否写入vars
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CallMode {
+    #[default]
+    Flow,
+    Detached {
+        keep_var: bool,
+    },
 }
 impl Display for ServiceEntity {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -34,6 +48,8 @@ impl Default for ServiceEntity {
             service_name: "".to_string(),
             node_name: "".to_string(),
             config: Box::new(()),
+            output_rule: None,
+            call_mode: CallMode::Flow,
         }
     }
 }
@@ -71,6 +87,10 @@ impl ServiceEntity {
         self.config = Box::new(config);
         self
     }
+    pub fn set_output_rule<J: Into<JsonInput>>(mut self, rule: J) -> Self {
+        self.output_rule = Some(rule.into());
+        self
+    }
     pub fn deref_mut_transform_config<F, T: Any, Out>(&mut self, transform_func: F) -> Out
     where
         F: FnOnce(Option<&T>) -> Out,
@@ -102,6 +122,8 @@ pub struct ServiceEntityJson {
     pub service_name: String,
     pub node_name: String,
     pub config: JsonInput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_rule: Option<JsonInput>,
 }
 impl TryFrom<ServiceEntity> for ServiceEntityJson {
     type Error = ServiceEntity;
@@ -115,15 +137,18 @@ impl TryFrom<ServiceEntity> for ServiceEntityJson {
             service_name: value.service_name,
             node_name: value.node_name,
             config: *config,
+            output_rule: value.output_rule,
         })
     }
 }
 
 impl From<ServiceEntityJson> for ServiceEntity {
     fn from(value: ServiceEntityJson) -> Self {
-        ServiceEntity::new(value.config)
+        let mut se = ServiceEntity::new(value.config)
             .set_node_name(value.node_name)
-            .set_service_name(value.service_name)
+            .set_service_name(value.service_name);
+        se.output_rule = value.output_rule;
+        se
     }
 }
 
@@ -144,6 +169,10 @@ impl ServiceEntityJson {
         self.config = config.into();
         self
     }
+    pub fn set_output_rule<C: Into<JsonInput>>(mut self, rule: C) -> Self {
+        self.output_rule = Some(rule.into());
+        self
+    }
 }
 
 // impl TryFrom<&str> for ServiceEntityJson {
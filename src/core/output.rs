@@ -1,5 +1,5 @@
 use crate::core::Ctx;
-use crate::utils::string;
+use crate::utils::{expr, path, string};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
@@ -86,20 +86,7 @@ impl OutputObject for Value {
     }
 
     fn get_val(&self, key: &str) -> Option<Value> {
-        if key=="*" {
-            return Some(self.clone());
-        }
-        let ks = key.splitn(2, ".").collect::<Vec<_>>();
-        if let Value::Object(obj) = self {
-            if let Some(val) = obj.get(ks[0]) {
-                if ks.len() == 1 {
-                    return Some(val.clone());
-                } else {
-                    return Self::get_val(val, ks[1]);
-                }
-            }
-        }
-        None
+        path::query(self, key).ok()
     }
     fn set_value(&mut self, key: &str, val: Value) {
         let ss = key.splitn(2, ".").collect::<Vec<_>>();
@@ -226,6 +213,7 @@ pub enum Tran {
     Value(Value),
     Quote(String),
     Format(Vec<String>),
+    Expr(String),
 }
 
 impl Tran {
@@ -235,6 +223,32 @@ impl Tran {
     pub fn quote<S: Into<String>>(quote: S) -> Self {
         Tran::Quote(quote.into())
     }
+    pub fn expr<S: Into<String>>(expr: S) -> Self {
+        Tran::Expr(expr.into())
+    }
+}
+
+//format插值时非字符串值的渲染方式，字符串始终原样插入
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormatRender {
+    #[default]
+    Compact,
+    Pretty,
+    Yaml,
+}
+
+impl FormatRender {
+    pub fn render(&self, val: &Value) -> String {
+        match (self, val) {
+            (_, Value::String(s)) => s.clone(),
+            (FormatRender::Compact, _) => val.to_string(),
+            (FormatRender::Pretty, _) => {
+                serde_json::to_string_pretty(val).unwrap_or_else(|_| val.to_string())
+            }
+            (FormatRender::Yaml, _) => string::to_yaml(val),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -242,17 +256,101 @@ impl Tran {
 pub struct JsonInput {
     none_quote_skip: bool,
     transform_rule: HashMap<String, Tran>,
-    default_json: Value, // 通过${{xxx.xxx}}的形式插入变量
+    default_json: Value, // 通过${{xxx.xxx}}的形式插入变量，\${{表示字面量${{
+    format_render: FormatRender,
+    //default_json中原样保留、不渲染模板的顶层字段，由service在运行时自行解析
+    #[serde(skip)]
+    raw_fields: Vec<String>,
+    //JsonSchemaService附加的输入schema，由默认的input在转换后校验
+    #[serde(skip)]
+    input_schema: Option<Value>,
+    //pipeline隐式引用的上一步输出，没有"*"规则时生效：
+    //是对象时作为基础值，由其他规则覆盖字段；否则只在没有其他规则时整体作为输入
+    #[serde(skip)]
+    prev_base: Option<String>,
 }
 
 impl JsonInput {
     pub fn is_empty(&self) -> bool {
         self.transform_rule.is_empty() && self.default_json.is_null()
     }
+    pub fn has_transform_rule(&self, pos: &str) -> bool {
+        self.transform_rule.contains_key(pos)
+    }
+    pub fn is_none_quote_skip(&self) -> bool {
+        self.none_quote_skip
+    }
+    //收集所有引用路径：quote、format以及default_json中的${{xxx}}
+    pub fn quote_paths(&self) -> Vec<String> {
+        let mut list = vec![];
+        for tran in self.transform_rule.values() {
+            match tran {
+                Tran::Value(_) => {}
+                Tran::Quote(q) => list.push(q.clone()),
+                Tran::Format(fs) => fs
+                    .iter()
+                    .for_each(|x| list.extend(expr::template_paths(x))),
+                Tran::Expr(e) => list.extend(expr::template_paths(e)),
+            }
+        }
+        Self::collect_template_paths(&self.default_json, &mut list);
+        list
+    }
+    fn collect_template_paths(val: &Value, list: &mut Vec<String>) {
+        match val {
+            Value::String(s) => string::extract_template_content(s)
+                .iter()
+                .for_each(|x| list.extend(expr::template_paths(x))),
+            Value::Array(arr) => arr
+                .iter()
+                .for_each(|v| Self::collect_template_paths(v, list)),
+            Value::Object(obj) => obj
+                .values()
+                .for_each(|v| Self::collect_template_paths(v, list)),
+            _ => {}
+        }
+    }
+    //获取某个位置上的字面量，模板字符串不算字面量
+    pub fn literal_value(&self, pos: &str) -> Option<Value> {
+        match self.transform_rule.get(pos) {
+            Some(Tran::Value(v)) => return Some(v.clone()),
+            Some(_) => return None,
+            None => {}
+        }
+        let val = self.default_json.get_val(pos)?;
+        if let Value::String(ref s) = val {
+            if !string::extract_template_content(s).is_empty() {
+                return None;
+            }
+        }
+        Some(val)
+    }
     pub fn skip_null_quote(mut self) -> Self {
         self.none_quote_skip = true;
         self
     }
+    pub fn set_format_render(mut self, render: FormatRender) -> Self {
+        self.format_render = render;
+        self
+    }
+    pub fn set_raw_fields<S: Into<String>, I: IntoIterator<Item = S>>(
+        mut self,
+        fields: I,
+    ) -> Self {
+        self.raw_fields = fields.into_iter().map(|x| x.into()).collect();
+        self
+    }
+    pub fn set_input_schema(mut self, schema: Option<Value>) -> Self {
+        self.input_schema = schema;
+        self
+    }
+    pub fn input_schema(&self) -> Option<&Value> {
+        self.input_schema.as_ref()
+    }
+    pub fn set_prev_base<S: Into<String>>(mut self, node: S) -> Self {
+        self.prev_base = Some(node.into());
+        self
+    }
     pub fn set_default_json(mut self, default_json: Value) -> Self {
         self.default_json = default_json;
         self
@@ -351,15 +449,43 @@ impl JsonInput {
         val_name: String,
         val: Value,
     ) -> anyhow::Result<()> {
-        let ss = pos.splitn(2, ".").collect::<Vec<_>>();
+        let t = match Self::get_mut_by_pos(t, pos) {
+            Some(t) => t,
+            None => return anyhow::anyhow!("JsonInput.to format not found pos[{}]", pos).err(),
+        };
+        //val_name可以是${{xxx}}或者模板内容xxx
+        let name = string::extract_template_content(val_name.as_str())
+            .pop()
+            .unwrap_or(val_name);
         match t {
             Value::String(s) => {
-                *s = s.replace(val_name.as_str(), val.to_string().as_str());
+                *s = string::render_template(s, |c| {
+                    if c == name {
+                        Some(FormatRender::Compact.render(&val))
+                    } else {
+                        None
+                    }
+                });
                 Ok(())
             }
-            _ => anyhow::anyhow!("JsonInput.to format only support string, pos[{}]", ss[0]).err(),
+            _ => anyhow::anyhow!("JsonInput.to format only support string, pos[{}]", pos).err(),
         }
     }
+    //pos为空或*时返回t本身
+    fn get_mut_by_pos<'a>(t: &'a mut Value, pos: &str) -> Option<&'a mut Value> {
+        if pos.is_empty() || pos == "*" {
+            return Some(t);
+        }
+        let mut t = t;
+        for k in pos.split('.') {
+            t = match t {
+                Value::Array(list) => list.get_mut(usize::from_str(k).ok()?)?,
+                Value::Object(map) => map.get_mut(k)?,
+                _ => return None,
+            };
+        }
+        Some(t)
+    }
     pub fn remove_val_from_json_val(t: &mut Value, pos: &str) -> anyhow::Result<Value> {
         if pos == "*" {
             let val = std::mem::replace(t, Value::Null);
@@ -418,12 +544,19 @@ impl JsonInput {
                 if self.transform_rule.contains_key(path.as_str()) {
                     return false;
                 }
-                let mut list = string::extract_template_content(s);
-                if list.len() == 1 && list[0].len() == (s.len() - 5) {
-                    self.transform_rule
-                        .insert(path, Tran::Quote(list.remove(0)));
+                let list = string::extract_template_content(s);
+                if let Some(content) = string::whole_template(s) {
+                    //根节点为模板时，引用整个输入
+                    let path = if path.is_empty() { "*".into() } else { path };
+                    //单纯的路径(或无法解析的内容)保持quote语义，其余按表达式求值
+                    let tran = match expr::parse(content.as_str()) {
+                        Ok(e) if e.as_path().is_none() => Tran::Expr(content),
+                        _ => Tran::Quote(content),
+                    };
+                    self.transform_rule.insert(path, tran);
                     return true;
-                } else if list.len() > 0 {
+                } else if !list.is_empty() || string::has_escaped_template(s) {
+                    //只有转义模板时也需要经过format还原
                     self.transform_rule.insert(path, Tran::Format(list));
                 }
             }
@@ -440,6 +573,9 @@ impl JsonInput {
             Value::Object(obj) => {
                 let mut remove_list = vec![];
                 for (k, v) in obj.iter_mut() {
+                    if path.is_empty() && self.raw_fields.contains(k) {
+                        continue;
+                    }
                     let p = if path.is_empty() {
                         k.clone()
                     } else {
@@ -488,22 +624,43 @@ impl JsonInput {
         data_source: &mut Option<Value>,
     ) -> anyhow::Result<Value> {
         if let Some(val) = data_source {
-            return Self::remove_val_from_json_val(val, pos);
-        }
-        let ss = pos.splitn(2, ".").collect::<Vec<_>>();
-        let node = ss[0];
-        let res = if ss.len() > 1 {
-            if let Some(val) = ctx.get_var_field(node, ss[1]).await {
-                val
-            }else{
-                return anyhow::anyhow!("JsonInput.to not found node.field[{}] from metadata", pos)
-                    .err();
+            //普通路径直接取出，避免clone
+            if path::is_plain(pos) {
+                return Self::remove_val_from_json_val(val, pos);
             }
-            
-        } else { 
-            ctx.get_var(node).await
+            return path::query(val, pos);
+        }
+        let (node, field) = path::split_root(pos);
+        if field.is_empty() {
+            return Ok(ctx.get_var(node).await);
+        }
+        match ctx.try_get_var_field(node, field).await {
+            Ok(val) => Ok(val),
+            Err(e) => anyhow::anyhow!("JsonInput.to quote[{}] from metadata: {}", pos, e).err(),
+        }
+    }
+    //表达式中引用的路径不存在时取null，不会消耗data_source
+    async fn get_expr_from_ctx(
+        content: &str,
+        ctx: &Ctx,
+        data_source: &Option<Value>,
+    ) -> anyhow::Result<Value> {
+        let e = match expr::parse(content) {
+            Ok(e) => e,
+            Err(_) => return Self::get_var_from_ctx(content, ctx, &mut data_source.clone()).await,
         };
-        Ok(res)
+        if let Some(p) = e.as_path() {
+            return Self::get_var_from_ctx(p, ctx, &mut data_source.clone()).await;
+        }
+        let mut vars = HashMap::new();
+        for p in e.paths() {
+            let v = match data_source {
+                Some(ds) => ds.get_val(p.as_str()),
+                None => Self::get_var_from_ctx(p.as_str(), ctx, &mut None).await.ok(),
+            };
+            vars.insert(p, v);
+        }
+        e.eval(&|p| vars.get(p).cloned().flatten())
     }
     pub async fn transform(
         mut self,
@@ -512,7 +669,49 @@ impl JsonInput {
         mut data_source: Option<Value>,
     ) -> anyhow::Result<()> {
         let mut default_json = self.default_json.take();
-        self.default_json_make_rule(&mut default_json, "".into());
+        if self.default_json_make_rule(&mut default_json, "".into()) {
+            default_json = Value::Null;
+        }
+        if let Some(prev) = self.prev_base.take() {
+            if !self.transform_rule.contains_key("*") {
+                let whole = self.transform_rule.is_empty() && default_json.is_null();
+                let base = ctx.get_var(prev.as_str()).await;
+                if whole || base.is_object() {
+                    Self::cover_default(base, val);
+                }
+            }
+        }
+        //"*"规则作为整个输入的基础值，再由default_json和其他规则覆盖
+        if let Some(base) = self.transform_rule.remove("*") {
+            let base = match base {
+                Tran::Value(v) => Some(v),
+                Tran::Quote(q) => match Self::get_var_from_ctx(&q, &ctx, &mut data_source).await {
+                    Ok(v) => Some(v),
+                    Err(e) => {
+                        if !self.none_quote_skip {
+                            return Err(e);
+                        }
+                        None
+                    }
+                },
+                Tran::Expr(e) => match Self::get_expr_from_ctx(&e, &ctx, &data_source).await {
+                    Ok(v) => Some(v),
+                    Err(e) => {
+                        if !self.none_quote_skip {
+                            return Err(e);
+                        }
+                        None
+                    }
+                },
+                Tran::Format(_) => {
+                    return anyhow::anyhow!("JsonInput.transform position[*] not support format")
+                        .err()
+                }
+            };
+            if let Some(base) = base {
+                Self::cover_default(base, val);
+            }
+        }
         Self::cover_default(default_json, val);
 
         for (k, v) in self.transform_rule {
@@ -532,16 +731,22 @@ impl JsonInput {
                         }
                     };
                 }
+                Tran::Expr(e) => match Self::get_expr_from_ctx(&e, &ctx, &data_source).await {
+                    Ok(v) => {
+                        Self::insert_val_to_json_val(val, k.as_str(), v)?;
+                    }
+                    Err(e) => {
+                        if !self.none_quote_skip {
+                            return Err(e);
+                        }
+                    }
+                },
                 Tran::Format(list) => {
+                    let mut vars = HashMap::new();
                     for i in list {
-                        match Self::get_var_from_ctx(i.as_str(), &ctx, &mut data_source).await {
+                        match Self::get_expr_from_ctx(i.as_str(), &ctx, &data_source).await {
                             Ok(v) => {
-                                Self::format_val_to_json_str(
-                                    val,
-                                    k.as_str(),
-                                    format!("${{{{{}}}}}", i),
-                                    v,
-                                )?;
+                                vars.insert(i, v);
                             }
                             Err(e) => {
                                 if !self.none_quote_skip {
@@ -550,18 +755,48 @@ impl JsonInput {
                             }
                         };
                     }
+                    let target = match Self::get_mut_by_pos(val, k.as_str()) {
+                        Some(s) => s,
+                        None => {
+                            return anyhow::anyhow!("JsonInput.to format not found pos[{}]", k)
+                                .err()
+                        }
+                    };
+                    let render = self.format_render;
+                    match target {
+                        Value::String(s) => {
+                            *s = string::render_template(s, |c| {
+                                vars.get(c).map(|v| render.render(v))
+                            });
+                        }
+                        _ => {
+                            return anyhow::anyhow!(
+                                "JsonInput.to format only support string, pos[{}]",
+                                k
+                            )
+                            .err()
+                        }
+                    }
                 }
             };
         }
         Ok(())
     }
-    pub async fn default_transform<T: Serialize + DeserializeOwned + Default>(
+    //以T::default()为底，返回反序列化前的值
+    pub async fn default_transform_value<T: Serialize + Default>(
         self,
         ctx: Ctx,
-    ) -> anyhow::Result<T> {
+    ) -> anyhow::Result<Value> {
         let val = T::default();
         let mut val = serde_json::to_value(val)?;
         self.transform(ctx, &mut val, None).await?;
+        Ok(val)
+    }
+    pub async fn default_transform<T: Serialize + DeserializeOwned + Default>(
+        self,
+        ctx: Ctx,
+    ) -> anyhow::Result<T> {
+        let val = self.default_transform_value::<T>(ctx).await?;
         let val = match serde_json::from_value(val) {
             Ok(val) => val,
             Err(e) => {
@@ -583,7 +818,7 @@ impl TryFrom<&str> for JsonInput {
 
 #[cfg(test)]
 mod test {
-    use crate::core::{Ctx, EngineRT, JsonInput, Output, OutputObject};
+    use crate::core::{Ctx, EngineRT, JsonInput, Output, OutputObject, Tran};
     use serde::{Deserialize, Serialize};
     use serde_json::{json, Value};
     use std::any::{Any, TypeId};
@@ -631,6 +866,46 @@ mod test {
         println!("--->{:?}", t)
     }
 
+    #[tokio::test]
+    async fn test_input_expr() {
+        let ctx = Ctx::new(EngineRT::default().build(), ());
+        ctx.insert_var("search", json!({"total":3,"tags":["a","b"],"name":"art"}))
+            .await;
+        ctx.insert_var("tool-search", json!({"result":"ok"})).await;
+
+        let mut val = json!({});
+        JsonInput::default()
+            .set_default_json(json!({
+                "count": "${{ search.total * 2 + 1 }}",
+                "level": "${{ search.total > 2 ? 'many' : 'few' }}",
+                "title": "${{ search.name | upper }}",
+                "lang": "${{ search.lang | default('en') }}",
+                "tags": "${{ search.tags | join('|') }}",
+                "text": "found ${{ search.tags | length }} tags",
+                "last": "${{ search.tags[-1] }}",
+                "prompt": "hi ${{ search.name }}, tags: ${{ search.tags }}, \\${{ keep }}",
+                "escaped": "\\${{ keep }}",
+                "price": "price: $${{ search.total }}",
+                "hyphen": "${{tool-search.result}}",
+            }))
+            .add_transform_rule("raw", Tran::expr("search.tags | json"))
+            .transform(ctx, &mut val, None)
+            .await
+            .unwrap();
+        assert_eq!(val["count"], 7);
+        assert_eq!(val["level"], "many");
+        assert_eq!(val["title"], "ART");
+        assert_eq!(val["lang"], "en");
+        assert_eq!(val["tags"], "a|b");
+        assert_eq!(val["text"], "found 2 tags");
+        assert_eq!(val["raw"], r#"["a","b"]"#);
+        assert_eq!(val["last"], "b");
+        assert_eq!(val["hyphen"], "ok");
+        assert_eq!(val["prompt"], r#"hi art, tags: ["a","b"], ${{ keep }}"#);
+        assert_eq!(val["escaped"], "${{ keep }}");
+        assert_eq!(val["price"], "price: $3");
+    }
+
     #[test]
     fn test_json_input_from() {
         // let ji = JsonInput::default().skip_null_quote()
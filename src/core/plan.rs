@@ -1,4 +1,4 @@
-use crate::core::{Ctx, ServiceEntity};
+use crate::core::{Ctx, ServiceEntity, ServiceEntityJson};
 use wd_tools::PFErr;
 
 #[derive(Debug)]
@@ -22,6 +22,16 @@ pub trait Plan: Send {
     fn next(&mut self, ctx: Ctx, name: &str) -> anyhow::Result<NextPlan>;
 
     fn set_to(&mut self, _name: &str, _to: Vec<String>) {}
+    //运行时修改plan，可以在service中通过ctx.deref_mut_plan调用
+    fn add_node(&mut self, node: ServiceEntityJson) -> anyhow::Result<()> {
+        anyhow::anyhow!("this plan not support add node[{}]", node.node_name).err()
+    }
+    fn add_edge(&mut self, from: &str, to: &str) -> anyhow::Result<()> {
+        anyhow::anyhow!("this plan not support add edge[{from} -> {to}]").err()
+    }
+    fn remove_pending_node(&mut self, name: &str) -> anyhow::Result<()> {
+        anyhow::anyhow!("this plan not support remove node[{name}]").err()
+    }
 }
 
 impl Plan for () {
@@ -33,3 +43,33 @@ impl Plan for () {
         anyhow::anyhow!("this is empty plan!!!").err()
     }
 }
+
+impl Plan for Box<dyn Plan + Sync + 'static> {
+    fn show_plan(&self) -> String {
+        self.as_ref().show_plan()
+    }
+    fn start_node_name(&self) -> &str {
+        self.as_ref().start_node_name()
+    }
+    fn end_node_name(&self) -> &str {
+        self.as_ref().end_node_name()
+    }
+    fn get(&mut self, name: &str) -> Option<ServiceEntity> {
+        self.as_mut().get(name)
+    }
+    fn next(&mut self, ctx: Ctx, name: &str) -> anyhow::Result<NextPlan> {
+        self.as_mut().next(ctx, name)
+    }
+    fn set_to(&mut self, name: &str, to: Vec<String>) {
+        self.as_mut().set_to(name, to)
+    }
+    fn add_node(&mut self, node: ServiceEntityJson) -> anyhow::Result<()> {
+        self.as_mut().add_node(node)
+    }
+    fn add_edge(&mut self, from: &str, to: &str) -> anyhow::Result<()> {
+        self.as_mut().add_edge(from, to)
+    }
+    fn remove_pending_node(&mut self, name: &str) -> anyhow::Result<()> {
+        self.as_mut().remove_pending_node(name)
+    }
+}
@@ -0,0 +1,230 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+// 由T::default()的序列化结果推导一个宽松的schema：只约束字段类型，null字段不做约束
+pub fn derive_schema<T: Serialize + Default>() -> Value {
+    match serde_json::to_value(T::default()) {
+        Ok(v) => schema_from_value(&v),
+        Err(_) => json!({}),
+    }
+}
+
+pub fn schema_from_value(val: &Value) -> Value {
+    match val {
+        Value::Null => json!({}),
+        Value::Bool(_) => json!({"type":"boolean"}),
+        Value::Number(n) if n.is_f64() => json!({"type":"number"}),
+        Value::Number(_) => json!({"type":"integer"}),
+        Value::String(_) => json!({"type":"string"}),
+        Value::Array(list) => match list.first() {
+            Some(v) => json!({"type":"array","items":schema_from_value(v)}),
+            None => json!({"type":"array"}),
+        },
+        Value::Object(obj) => {
+            let props = obj
+                .iter()
+                .map(|(k, v)| (k.clone(), schema_from_value(v)))
+                .collect::<Map<_, _>>();
+            json!({"type":"object","properties":props})
+        }
+    }
+}
+
+fn type_of(val: &Value) -> &'static str {
+    match val {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn match_type(ty: &str, val: &Value) -> bool {
+    match (ty, val) {
+        ("number", Value::Number(_)) => true,
+        ("integer", Value::Number(n)) => n.as_f64().map(|x| x.fract() == 0.0).unwrap_or(false),
+        _ => ty == type_of(val),
+    }
+}
+
+// 校验val，返回所有字段级错误，形如 input.query: expected string, got null
+pub fn validate_schema(schema: &Value, val: &Value, path: &str) -> Vec<String> {
+    let mut errs = vec![];
+    validate(schema, val, path, &mut errs);
+    errs
+}
+
+fn validate(schema: &Value, val: &Value, path: &str, errs: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Object(s) => s,
+        Value::Bool(false) => {
+            errs.push(format!("{}: is not allowed", path));
+            return;
+        }
+        _ => return,
+    };
+    let types = match schema.get("type") {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(ts)) => ts.iter().filter_map(|x| x.as_str()).collect(),
+        _ => vec![],
+    };
+    if !types.is_empty() && !types.iter().any(|t| match_type(t, val)) {
+        errs.push(format!(
+            "{}: expected {}, got {}",
+            path,
+            types.join("|"),
+            type_of(val)
+        ));
+        return;
+    }
+    if let Some(Value::Array(list)) = schema.get("enum") {
+        if !list.contains(val) {
+            errs.push(format!(
+                "{}: {} is not one of {}",
+                path,
+                val,
+                Value::Array(list.clone())
+            ));
+        }
+    }
+    if let Some(Value::Array(list)) = schema.get("anyOf") {
+        if !list
+            .iter()
+            .any(|s| validate_schema(s, val, path).is_empty())
+        {
+            errs.push(format!("{}: does not match any schema of anyOf", path));
+        }
+    }
+    let num = |k: &str| schema.get(k).and_then(|x| x.as_f64());
+    match val {
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(0.0);
+            if let Some(min) = num("minimum").filter(|m| n < *m) {
+                errs.push(format!("{}: {} is less than minimum {}", path, n, min));
+            }
+            if let Some(max) = num("maximum").filter(|m| n > *m) {
+                errs.push(format!("{}: {} is greater than maximum {}", path, n, max));
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as f64;
+            if let Some(min) = num("minLength").filter(|m| len < *m) {
+                errs.push(format!(
+                    "{}: length {} is less than minLength {}",
+                    path, len, min
+                ));
+            }
+            if let Some(max) = num("maxLength").filter(|m| len > *m) {
+                errs.push(format!(
+                    "{}: length {} is greater than maxLength {}",
+                    path, len, max
+                ));
+            }
+            if let Some(p) = schema.get("pattern").and_then(|x| x.as_str()) {
+                match Regex::new(p) {
+                    Ok(re) if !re.is_match(s) => {
+                        errs.push(format!("{}: does not match pattern {}", path, p))
+                    }
+                    Err(e) => errs.push(format!("{}: invalid pattern {}: {}", path, p, e)),
+                    _ => {}
+                }
+            }
+        }
+        Value::Array(list) => {
+            let len = list.len() as f64;
+            if let Some(min) = num("minItems").filter(|m| len < *m) {
+                errs.push(format!(
+                    "{}: {} items is less than minItems {}",
+                    path, len, min
+                ));
+            }
+            if let Some(max) = num("maxItems").filter(|m| len > *m) {
+                errs.push(format!(
+                    "{}: {} items is greater than maxItems {}",
+                    path, len, max
+                ));
+            }
+            if let Some(items) = schema.get("items") {
+                for (i, v) in list.iter().enumerate() {
+                    validate(items, v, format!("{}[{}]", path, i).as_str(), errs);
+                }
+            }
+        }
+        Value::Object(obj) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for k in required.iter().filter_map(|x| x.as_str()) {
+                    if !obj.contains_key(k) {
+                        errs.push(format!("{}.{}: is required", path, k));
+                    }
+                }
+            }
+            let props = schema.get("properties").and_then(|x| x.as_object());
+            for (k, v) in obj.iter() {
+                let p = format!("{}.{}", path, k);
+                match props.and_then(|x| x.get(k)) {
+                    Some(s) => validate(s, v, p.as_str(), errs),
+                    None => {
+                        if let Some(s) = schema.get("additionalProperties") {
+                            validate(s, v, p.as_str(), errs);
+                        }
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+
+    #[test]
+    fn test_schema() {
+        #[derive(Default, Serialize, Deserialize)]
+        struct Query {
+            query: String,
+            top_k: usize,
+            rate: f64,
+            tags: Vec<String>,
+            filter: Option<String>,
+        }
+        let schema = derive_schema::<Query>();
+        assert_eq!(schema["properties"]["query"]["type"], "string");
+        assert_eq!(schema["properties"]["top_k"]["type"], "integer");
+        assert_eq!(schema["properties"]["filter"], json!({}));
+
+        let val = json!({"query":null,"top_k":1.5,"rate":1,"tags":["a"],"filter":null});
+        let errs = validate_schema(&schema, &val, "input");
+        assert_eq!(
+            errs,
+            vec![
+                "input.query: expected string, got null",
+                "input.top_k: expected integer, got number"
+            ]
+        );
+
+        let schema = json!({
+            "type":"object",
+            "required":["name"],
+            "additionalProperties":false,
+            "properties":{
+                "name":{"type":"string","minLength":2,"pattern":"^[a-z]+$"},
+                "level":{"enum":["low","high"]},
+                "list":{"type":"array","maxItems":2,"items":{"type":"integer","minimum":0}}
+            }
+        });
+        let errs = validate_schema(
+            &schema,
+            &json!({"name":"a","level":"mid","list":[1,-1,2],"x":1}),
+            "output",
+        );
+        assert_eq!(errs.len(), 5, "{:?}", errs);
+        assert!(validate_schema(&schema, &json!({"name":"art","list":[1]}), "output").is_empty());
+    }
+}
@@ -1,10 +1,19 @@
-use crate::core::{Ctx, JsonService, JsonServiceExt, Output, ServiceEntity};
+use crate::core::{
+    derive_schema, Ctx, JsonSchemaService, JsonService, JsonServiceExt, Output, ServiceEntity,
+};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
 use wd_tools::PFArc;
 
 #[async_trait::async_trait]
 pub trait Service: Send {
+    fn input_schema(&self) -> Option<Value> {
+        None
+    }
+    fn output_schema(&self) -> Option<Value> {
+        None
+    }
     async fn call(&self, ctx: Ctx, node: ServiceEntity) -> anyhow::Result<Output>;
 }
 
@@ -22,6 +31,10 @@ pub trait ServiceLoader: Send {
     // async fn insert(&mut self,_name:&str,_service:Arc<dyn Service + Sync + 'static>){}
     // async fn remove(&mut self, _name: &str) -> Option<Arc<dyn Service + Sync + 'static>>{None}
     async fn load(&self, name: &str) -> Option<Arc<dyn Service + Sync + 'static>>;
+    //可枚举的service名，不支持枚举的loader返回空
+    fn names(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
 #[async_trait::async_trait]
@@ -58,10 +71,33 @@ impl MapServiceLoader {
             .insert(name.into(), JsonService::new(service).arc());
         self
     }
+    //schema为None时由In/Out的默认值推导
+    pub fn register_json_ext_service_with_schema<N: Into<String>, T, In, Out>(
+        self,
+        name: N,
+        service: T,
+        input_schema: Option<Value>,
+        output_schema: Option<Value>,
+    ) -> Self
+    where
+        T: JsonServiceExt<In, Out> + Sync + 'static,
+        In: serde::Serialize + serde::de::DeserializeOwned + Send + Sync + Default + 'static,
+        Out: serde::Serialize + Send + Sync + Default + 'static,
+    {
+        let service = JsonSchemaService::new(service)
+            .set_input_schema(input_schema.unwrap_or_else(derive_schema::<In>))
+            .set_output_schema(output_schema.unwrap_or_else(derive_schema::<Out>));
+        self.register_json_ext_service(name, service)
+    }
 }
 #[async_trait::async_trait]
 impl ServiceLoader for MapServiceLoader {
     async fn load(&self, name: &str) -> Option<Arc<dyn Service + Sync + 'static>> {
         self.map.get(name).map(|x| x.clone())
     }
+    fn names(&self) -> Vec<String> {
+        let mut list = self.map.keys().cloned().collect::<Vec<_>>();
+        list.sort();
+        list
+    }
 }
@@ -1,6 +1,9 @@
-use crate::core::{Ctx, JsonInput, Output, Service, ServiceEntity};
+use crate::core::{
+    derive_schema, validate_schema, Ctx, JsonInput, Output, Service, ServiceEntity, Usage,
+};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use serde_json::Value;
 use std::future::Future;
 use std::marker::PhantomData;
 use wd_tools::{PFErr, PFOk};
@@ -11,13 +14,46 @@ pub trait JsonServiceExt<
     Out: Serialize + Send + 'static,
 >: Send
 {
+    fn input_schema(&self) -> Option<Value> {
+        None
+    }
+    fn output_schema(&self) -> Option<Value> {
+        None
+    }
+    //default_json中不在节点入口渲染的顶层字段
+    fn raw_fields(&self) -> Vec<&'static str> {
+        vec![]
+    }
+    //从输出中提取用量，由JsonService记录到ctx并检查预算
+    fn usage(&self, _out: &Out) -> Option<Usage> {
+        None
+    }
     async fn input(&self, ctx: Ctx, se: &mut ServiceEntity) -> anyhow::Result<In> {
         let res = se.transform_config(|c: Option<JsonInput>| c);
         match res {
-            Some(s) => match s.default_transform(ctx).await {
-                Ok(o) => Ok(o),
-                Err(e) => anyhow::anyhow!("Node[{}] input transform error:{e}", se.node_name).err(),
-            },
+            Some(s) => {
+                let schema = s.input_schema().cloned().or_else(|| self.input_schema());
+                let s = s.set_raw_fields(self.raw_fields());
+                let val = match s.default_transform_value::<In>(ctx).await {
+                    Ok(o) => o,
+                    Err(e) => {
+                        return anyhow::anyhow!("Node[{}] input transform error:{e}", se.node_name)
+                            .err()
+                    }
+                };
+                if let Some(schema) = schema {
+                    let errs = validate_schema(&schema, &val, "input");
+                    if !errs.is_empty() {
+                        return anyhow::anyhow!("Node[{}] {}", se.node_name, errs.join("; ")).err();
+                    }
+                }
+                match serde_json::from_value(val) {
+                    Ok(o) => Ok(o),
+                    Err(e) => {
+                        anyhow::anyhow!("Node[{}] input transform error:{e}", se.node_name).err()
+                    }
+                }
+            }
             None => Err(anyhow::anyhow!(
                 "JsonServiceExt:{}.{} ServiceEntity config must json Value",
                 se.service_name,
@@ -49,6 +85,86 @@ where
     }
 }
 
+//为JsonServiceExt附加输入输出schema
+pub struct JsonSchemaService<T> {
+    inner: T,
+    input: Option<Value>,
+    output: Option<Value>,
+}
+
+impl<T> JsonSchemaService<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            input: None,
+            output: None,
+        }
+    }
+    pub fn set_input_schema(mut self, schema: Value) -> Self {
+        self.input = Some(schema);
+        self
+    }
+    pub fn set_output_schema(mut self, schema: Value) -> Self {
+        self.output = Some(schema);
+        self
+    }
+    //由In/Out的默认值推导schema
+    pub fn derive_schema<In: Serialize + Default, Out: Serialize + Default>(self) -> Self {
+        self.set_input_schema(derive_schema::<In>())
+            .set_output_schema(derive_schema::<Out>())
+    }
+}
+
+#[async_trait::async_trait]
+impl<T, In, Out> JsonServiceExt<In, Out> for JsonSchemaService<T>
+where
+    T: JsonServiceExt<In, Out> + Sync + 'static,
+    In: Serialize + DeserializeOwned + Send + Sync + Default + 'static,
+    Out: Serialize + Send + Sync + 'static,
+{
+    fn input_schema(&self) -> Option<Value> {
+        self.input.clone()
+    }
+    fn output_schema(&self) -> Option<Value> {
+        self.output.clone()
+    }
+    fn raw_fields(&self) -> Vec<&'static str> {
+        self.inner.raw_fields()
+    }
+    fn usage(&self, out: &Out) -> Option<Usage> {
+        self.inner.usage(out)
+    }
+    //schema随config交给inner默认的input，在唯一一次转换后校验；
+    //inner自定义input没有取走config时，改为校验它实际产出的输入
+    async fn input(&self, ctx: Ctx, se: &mut ServiceEntity) -> anyhow::Result<In> {
+        let schema = match self.input {
+            Some(ref s) => s,
+            None => return self.inner.input(ctx, se).await,
+        };
+        if let Some(cfg) = se.transform_config(|c: Option<JsonInput>| c) {
+            se.config = Box::new(cfg.set_input_schema(Some(schema.clone())));
+        }
+        let input = self.inner.input(ctx, se).await?;
+        let unchecked = se.deref_mut_transform_config(|c: Option<&JsonInput>| {
+            c.is_some_and(|x| x.input_schema().is_some())
+        });
+        if unchecked {
+            let val = serde_json::to_value(&input)?;
+            let errs = validate_schema(schema, &val, "input");
+            if !errs.is_empty() {
+                return anyhow::anyhow!("Node[{}] {}", se.node_name, errs.join("; ")).err();
+            }
+        }
+        Ok(input)
+    }
+    async fn output(&self, out: Out) -> anyhow::Result<Output> {
+        self.inner.output(out).await
+    }
+    async fn call(&self, ctx: Ctx, input: In, se: ServiceEntity) -> anyhow::Result<Out> {
+        self.inner.call(ctx, input, se).await
+    }
+}
+
 pub struct JsonService<T, In, Out> {
     inner: T,
     _in: PhantomData<In>,
@@ -76,18 +192,40 @@ where
     In: Serialize + DeserializeOwned + Send + Sync + Default + 'static,
     Out: Serialize + Send + Sync + 'static,
 {
+    fn input_schema(&self) -> Option<Value> {
+        self.inner.input_schema()
+    }
+    fn output_schema(&self) -> Option<Value> {
+        self.inner.output_schema()
+    }
     async fn call(&self, ctx: Ctx, mut node: ServiceEntity) -> anyhow::Result<Output> {
         let input = self.inner.input(ctx.clone(), &mut node).await?;
-        let output = JsonServiceExt::call(&self.inner, ctx, input, node).await?;
-        self.inner.output(output).await
+        let node_name = node.node_name.clone();
+        let output = JsonServiceExt::call(&self.inner, ctx.clone(), input, node).await?;
+        if let Some(usage) = self.inner.usage(&output) {
+            ctx.record_usage(node_name.as_str(), usage)?;
+        }
+        let output = self.inner.output(output).await?;
+        if let Some(schema) = self.inner.output_schema() {
+            let errs = validate_schema(&schema, &output.as_val(), "output");
+            if !errs.is_empty() {
+                return anyhow::anyhow!("Node[{}] {}", node_name, errs.join("; ")).err();
+            }
+        }
+        Ok(output)
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::core::service_json_ext::JsonService;
-    use crate::core::{Ctx, MapServiceLoader, ServiceEntity};
-    use serde_json::Value;
+    use crate::core::{
+        Ctx, CtxSerdeExt, EngineRT, JsonInput, JsonServiceExt, MapServiceLoader, ServiceEntity,
+    };
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::ServiceLoaderWrap;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
 
     #[tokio::test]
     async fn test_json_service_ext() {
@@ -98,4 +236,120 @@ mod test {
             }),
         );
     }
+
+    #[tokio::test]
+    async fn test_json_service_schema() {
+        #[derive(Default, Debug, Serialize, Deserialize)]
+        #[serde(default)]
+        struct Query {
+            query: String,
+            top_k: usize,
+        }
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service_with_schema(
+                        "search",
+                        |_c: Ctx, i: Query, _se: ServiceEntity| async move { Ok(i) },
+                        None,
+                        None,
+                    )
+                    .register_json_ext_service_with_schema(
+                        "rank",
+                        |_c: Ctx, i: Query, _se: ServiceEntity| async move { Ok(i) },
+                        None,
+                        Some(json!({"properties":{"top_k":{"maximum":3}}})),
+                    ),
+            )
+            .build();
+        let plan = |service: &str| {
+            Pipeline::default()
+                .step((
+                    service,
+                    JsonInput::default().add_transform_quote("query", "start.q"),
+                ))
+                .check()
+                .unwrap()
+        };
+
+        let res: Value = rt
+            .ctx(plan("search"))
+            .serde_run(json!({"q":"art"}))
+            .await
+            .unwrap();
+        assert_eq!(res, json!({"query":"art","top_k":0}));
+
+        let err = rt
+            .ctx(plan("search"))
+            .serde_run::<_, Value>(json!({"q":null}))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Node[search] input.query: expected string, got null"),
+            "{}",
+            err
+        );
+
+        let err = rt
+            .ctx(plan("rank"))
+            .serde_run::<_, Value>(json!({"q":"art","top_k":5}))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Node[rank] output.top_k: 5 is greater than maximum 3"),
+            "{}",
+            err
+        );
+
+        //包装后仍使用inner自定义的input，schema校验它实际产出的输入
+        struct Fixed;
+        #[async_trait::async_trait]
+        impl JsonServiceExt<Query, Query> for Fixed {
+            async fn input(&self, _ctx: Ctx, _se: &mut ServiceEntity) -> anyhow::Result<Query> {
+                Ok(Query {
+                    query: "fixed".into(),
+                    top_k: 1,
+                })
+            }
+            async fn call(&self, _c: Ctx, i: Query, _se: ServiceEntity) -> anyhow::Result<Query> {
+                Ok(i)
+            }
+        }
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service_with_schema(
+                        "fixed",
+                        Fixed,
+                        Some(json!({"properties":{"query":{"type":"string"}}})),
+                        None,
+                    )
+                    .register_json_ext_service_with_schema(
+                        "fixed_strict",
+                        Fixed,
+                        Some(json!({"properties":{"top_k":{"maximum":0}}})),
+                        None,
+                    ),
+            )
+            .build();
+        let res: Value = rt
+            .ctx(plan("fixed"))
+            .serde_run(json!({"q":1}))
+            .await
+            .unwrap();
+        assert_eq!(res, json!({"query":"fixed","top_k":1}));
+        let err = rt
+            .ctx(plan("fixed_strict"))
+            .serde_run::<_, Value>(json!({"q":"art"}))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Node[fixed_strict] input.top_k: 1 is greater than maximum 0"),
+            "{}",
+            err
+        );
+    }
 }
@@ -0,0 +1,203 @@
+use crate::core::Error;
+use std::collections::HashMap;
+
+#[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+}
+
+impl Usage {
+    pub fn new(prompt_tokens: u64, completion_tokens: u64) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            cost: 0.0,
+        }
+    }
+    pub fn set_cost(mut self, cost: f64) -> Self {
+        self.cost = cost;
+        self
+    }
+    //total_tokens为0时按prompt + completion计算
+    pub fn tokens(&self) -> u64 {
+        if self.total_tokens == 0 {
+            self.prompt_tokens + self.completion_tokens
+        } else {
+            self.total_tokens
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.tokens() == 0 && self.cost == 0.0
+    }
+    pub fn add(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.tokens();
+        self.cost += other.cost;
+    }
+}
+
+//0表示不限制
+#[derive(Default, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Budget {
+    pub max_tokens_per_run: u64,
+    pub max_cost_per_run: f64,
+}
+
+impl Budget {
+    pub fn set_max_tokens_per_run(mut self, max: u64) -> Self {
+        self.max_tokens_per_run = max;
+        self
+    }
+    pub fn set_max_cost_per_run(mut self, max: f64) -> Self {
+        self.max_cost_per_run = max;
+        self
+    }
+    pub fn check(&self, node: &str, usage: &Usage) -> Result<(), Error> {
+        if self.max_tokens_per_run > 0 && usage.tokens() > self.max_tokens_per_run {
+            return Err(Error::BudgetExceeded {
+                node: node.to_string(),
+                reason: format!(
+                    "tokens {} > max_tokens_per_run {}",
+                    usage.tokens(),
+                    self.max_tokens_per_run
+                ),
+            });
+        }
+        if self.max_cost_per_run > 0.0 && usage.cost > self.max_cost_per_run {
+            return Err(Error::BudgetExceeded {
+                node: node.to_string(),
+                reason: format!(
+                    "cost {} > max_cost_per_run {}",
+                    usage.cost, self.max_cost_per_run
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+//一次运行的用量统计，fork出的子ctx共享同一份
+#[derive(Default, Debug, Clone)]
+pub struct UsageLedger {
+    pub budget: Budget,
+    pub total: Usage,
+    pub nodes: HashMap<String, Usage>,
+}
+
+impl UsageLedger {
+    pub fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            ..Default::default()
+        }
+    }
+    //先记录再检查，超出预算时返回BudgetExceeded
+    pub fn record(&mut self, node: &str, usage: &Usage) -> Result<(), Error> {
+        self.total.add(usage);
+        self.nodes.entry(node.to_string()).or_default().add(usage);
+        self.check(node)
+    }
+    pub fn check(&self, node: &str) -> Result<(), Error> {
+        self.budget.check(node, &self.total)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{
+        Budget, Ctx, CtxSerdeExt, EngineRT, Error, JsonInput, JsonServiceExt, ServiceEntity, Usage,
+    };
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::ServiceLoaderWrap;
+    use serde_json::{json, Value};
+
+    //每次调用消耗100 token
+    struct MockModel;
+
+    #[async_trait::async_trait]
+    impl JsonServiceExt<Value, Value> for MockModel {
+        fn usage(&self, _out: &Value) -> Option<Usage> {
+            Some(Usage::new(60, 40).set_cost(0.01))
+        }
+        async fn call(&self, _ctx: Ctx, input: Value, _se: ServiceEntity) -> anyhow::Result<Value> {
+            Ok(input)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_usage_budget() {
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("model", MockModel)
+                    //通过ctx上报用量
+                    .register_json_ext_service(
+                        "tool",
+                        |ctx: Ctx, input: Value, se: ServiceEntity| async move {
+                            ctx.record_usage(se.node_name.as_str(), Usage::new(5, 0))?;
+                            Ok(input)
+                        },
+                    ),
+            )
+            .set_budget(Budget::default().set_max_tokens_per_run(250))
+            .build();
+        let plan = |n: usize| {
+            let mut p = Pipeline::default();
+            for _ in 0..n {
+                p = p
+                    .step(("model", JsonInput::default()))
+                    .step(("tool", JsonInput::default()));
+            }
+            p.check().unwrap()
+        };
+
+        let ctx = rt.ctx(plan(2));
+        let res: Value = ctx.clone().serde_run(json!({"q":1})).await.unwrap();
+        assert_eq!(res, json!({"q":1}));
+        let usage = ctx.usage();
+        assert_eq!((usage.tokens(), usage.prompt_tokens), (210, 130));
+        assert!((usage.cost - 0.02).abs() < 1e-9);
+        let nodes = ctx.usage_by_node();
+        assert_eq!(nodes["model_2"].tokens(), 100);
+        assert_eq!(nodes["tool"].tokens(), 5);
+
+        //第三次调用模型后超出预算，后续节点不再执行
+        let ctx = rt.ctx(plan(3));
+        let err = ctx
+            .clone()
+            .serde_run::<_, Value>(json!({}))
+            .await
+            .unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::BudgetExceeded { node, reason }) => {
+                assert_eq!(node, "model_4");
+                assert_eq!(reason, "tokens 310 > max_tokens_per_run 250");
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert!(!ctx.usage_by_node().contains_key("tool_5"));
+
+        //单次运行可以覆盖engine的预算
+        let ctx = rt
+            .ctx(plan(1))
+            .set_budget(Budget::default().set_max_cost_per_run(0.005));
+        let se = ServiceEntity::new(JsonInput::default())
+            .set_node_name("detached")
+            .set_service_name("model");
+        let err = ctx.sub_call(se, false).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("cost 0.01 > max_cost_per_run 0.005"),
+            "{}",
+            err
+        );
+        assert_eq!(ctx.usage().tokens(), 100);
+    }
+}
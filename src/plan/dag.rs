@@ -1,6 +1,6 @@
 use crate::core::{Ctx, NextPlan, Plan, ServiceEntity, ServiceEntityJson};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::mem::take;
 use wd_tools::PFErr;
 
@@ -118,6 +118,9 @@ pub struct DAG {
     pub start: String,
     pub end: String,
     pub node_set: HashMap<String, DAGNode>,
+    //已经执行完成(调用过next)的节点
+    #[serde(skip)]
+    completed: HashSet<String>,
 }
 impl Plan for DAG {
     fn show_plan(&self) -> String {
@@ -142,6 +145,7 @@ impl Plan for DAG {
     }
 
     fn next(&mut self, _ctx: Ctx, name: &str) -> anyhow::Result<NextPlan> {
+        self.completed.insert(name.to_string());
         if name == self.end {
             return Ok(NextPlan::End);
         }
@@ -168,6 +172,62 @@ impl Plan for DAG {
             s.to = to;
         }
     }
+
+    fn add_node(&mut self, node: ServiceEntityJson) -> anyhow::Result<()> {
+        let name = node.node_name.clone();
+        if name.is_empty() {
+            return anyhow::anyhow!("add node failed: node_name is empty").err();
+        }
+        if self.node_set.contains_key(name.as_str()) {
+            return anyhow::anyhow!("add node failed: node[{}] already exists", name).err();
+        }
+        let node = DAGNode::new(name.clone()).set_service_entity(node);
+        self.node_set.insert(name, node);
+        Ok(())
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str) -> anyhow::Result<()> {
+        if !self.node_set.contains_key(from) {
+            return anyhow::anyhow!("add edge failed: node[{}] not found", from).err();
+        }
+        //已经完成的节点不会再向下调度，新边永远不会被执行
+        if self.completed.contains(from) {
+            return anyhow::anyhow!("add edge failed: node[{}] already completed", from).err();
+        }
+        if let Some(n) = self.node_set.get_mut(to) {
+            //已经调度的节点不能再增加前置节点
+            if n.service.is_none() {
+                return anyhow::anyhow!("add edge failed: node[{}] already scheduled", to).err();
+            }
+            n.add_from(from);
+        } else {
+            return anyhow::anyhow!("add edge failed: node[{}] not found", to).err();
+        }
+        if let Some(n) = self.node_set.get_mut(from) {
+            n.add_to(to);
+        }
+        Ok(())
+    }
+
+    fn remove_pending_node(&mut self, name: &str) -> anyhow::Result<()> {
+        if name == self.start || name == self.end {
+            return anyhow::anyhow!("remove node failed: node[{}] is start or end", name).err();
+        }
+        match self.node_set.get(name) {
+            Some(n) if n.service.is_none() => {
+                return anyhow::anyhow!("remove node failed: node[{}] already scheduled", name)
+                    .err()
+            }
+            Some(_) => {}
+            None => return anyhow::anyhow!("remove node failed: node[{}] not found", name).err(),
+        }
+        self.node_set.remove(name);
+        for n in self.node_set.values_mut() {
+            n.to.retain(|x| x != name);
+            n.from.retain(|x| x != name);
+        }
+        Ok(())
+    }
 }
 impl DAG {
     pub fn node<Node: Into<DAGNode>>(mut self, node: Node) -> Self {
@@ -296,8 +356,12 @@ impl DAG {
 
 #[cfg(test)]
 mod test {
-    use crate::core::Plan;
-    use crate::plan::dag::DAG;
+    use crate::core::{
+        Ctx, CtxSerdeExt, EngineRT, JsonInput, Plan, ServiceEntity, ServiceEntityJson,
+    };
+    use crate::plan::dag::{DAGNode, DAG};
+    use crate::service::ext::{Obj, ServiceLoaderWrap};
+    use serde_json::{json, Value};
 
     #[test]
     fn test_dag() {
@@ -323,4 +387,49 @@ mod test {
         );
         println!("success");
     }
+
+    #[tokio::test]
+    async fn test_dag_mutation() {
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("add", |_ctx, io: Obj, _se| async move {
+                        let io = Value::from(io);
+                        Ok(io["a"].as_i64().unwrap_or(0) + io["b"].as_i64().unwrap_or(0))
+                    })
+                    .register_json_ext_service(
+                        "planner",
+                        |ctx: Ctx, _input: Value, se: ServiceEntity| async move {
+                            let completed = ctx.deref_mut_plan(|p| {
+                                p.set_to(se.node_name.as_str(), vec![]);
+                                p.remove_pending_node("placeholder")?;
+                                p.add_node(ServiceEntityJson::from(r#"{"service_name":"add","node_name":"tool","config":{"transform_rule":{"a":{"quote":"start.number"},"b":{"value":1}}}}"#))?;
+                                p.add_edge(se.node_name.as_str(), "tool")?;
+                                p.add_edge("tool", "end")?;
+                                //start已经完成，这条边不会被执行
+                                Ok::<_, anyhow::Error>(p.add_edge("start", "tool").is_err())
+                            })?;
+                            Ok(completed)
+                        },
+                    ),
+            )
+            .build();
+        let plan = DAG::default()
+            .node(("start", r#"{"service_name":"start","config":{"transform_rule":{"number":{"quote":"number"}}}}"#))
+            .node(DAGNode::new("planner").set_service_entity(ServiceEntityJson::default().set_service_name("planner").set_config(JsonInput::default())))
+            .node(DAGNode::new("placeholder").set_service_entity(ServiceEntityJson::default().set_service_name("add").set_config(JsonInput::default())))
+            .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"result":{"quote":"tool"},"rejected":{"quote":"planner"}}}}"#))
+            .edges([("start", "planner"), ("planner", "placeholder"), ("placeholder", "end")])
+            .check()
+            .unwrap();
+
+        let mut check = plan.clone();
+        assert!(check.add_edge("planner", "not_exist").is_err());
+        //start已经调度，不能再增加前置节点
+        check.get("start");
+        assert!(check.add_edge("planner", "start").is_err());
+
+        let res: Value = rt.ctx(plan).serde_run(json!({"number":8})).await.unwrap();
+        assert_eq!(res, json!({"result":9,"rejected":true}));
+    }
 }
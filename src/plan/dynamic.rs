@@ -0,0 +1,247 @@
+use crate::core::{Ctx, NextPlan, Plan, ServiceEntity, ServiceEntityJson};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use wd_tools::PFErr;
+
+// 由router节点的输出决定下一个节点：start -> router -> node -> router -> ... -> end
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DynamicPlan {
+    pub start: String,
+    pub end: String,
+    pub router: String,
+    pub route_field: String,
+    pub allow: Vec<String>,
+    pub max_steps: usize,
+    pub steps: usize,
+    pub node_set: HashMap<String, ServiceEntityJson>,
+}
+
+impl DynamicPlan {
+    pub fn new<S: Into<String>>(router: S) -> Self {
+        Self {
+            start: "start".into(),
+            end: "end".into(),
+            router: router.into(),
+            route_field: "next".into(),
+            max_steps: 10,
+            ..Default::default()
+        }
+    }
+    pub fn node<N: Into<String>, E: Into<ServiceEntityJson>>(mut self, (name, se): (N, E)) -> Self {
+        let name = name.into();
+        let se = se.into().set_node_name(name.clone());
+        self.node_set.insert(name, se);
+        self
+    }
+    pub fn nodes<N: Into<String>, E: Into<ServiceEntityJson>, I: IntoIterator<Item = (N, E)>>(
+        mut self,
+        nodes: I,
+    ) -> Self {
+        for i in nodes {
+            self = self.node(i);
+        }
+        self
+    }
+    pub fn allow<S: Into<String>, I: IntoIterator<Item = S>>(mut self, nodes: I) -> Self {
+        for i in nodes {
+            let name = i.into();
+            if !self.allow.contains(&name) {
+                self.allow.push(name);
+            }
+        }
+        self
+    }
+    pub fn set_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+    pub fn set_route_field<S: Into<String>>(mut self, field: S) -> Self {
+        self.route_field = field.into();
+        self
+    }
+    pub fn set_start_node_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.start = name.into();
+        self
+    }
+    pub fn set_end_node_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.end = name.into();
+        self
+    }
+    pub fn check(self) -> anyhow::Result<Self> {
+        for (k, i) in [
+            ("start", &self.start),
+            ("end", &self.end),
+            ("router", &self.router),
+        ] {
+            if !self.node_set.contains_key(i) {
+                return anyhow::anyhow!("not found {} node[{}]", k, i).err();
+            }
+        }
+        if self.router == self.start || self.router == self.end {
+            return anyhow::anyhow!("router node[{}] can not be start or end", self.router).err();
+        }
+        for i in self.allow.iter() {
+            if !self.node_set.contains_key(i) {
+                return anyhow::anyhow!("not found allowed node[{}]", i).err();
+            }
+            if i == &self.start || i == &self.router {
+                return anyhow::anyhow!("node[{}] can not be allowed", i).err();
+            }
+        }
+        for (k, v) in self.node_set.iter() {
+            if v.service_name.is_empty() {
+                return anyhow::anyhow!("node[{}].service.name is empty", k).err();
+            }
+        }
+        Ok(self)
+    }
+    fn route(&self, ctx: &Ctx) -> anyhow::Result<String> {
+        let out = ctx.deref_var(self.router.as_str());
+        let next = match &out {
+            Value::String(s) => Some(s.clone()),
+            Value::Object(_) => match out.get(self.route_field.as_str()) {
+                Some(Value::String(s)) => Some(s.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        match next {
+            Some(s) => Ok(s),
+            None => anyhow::anyhow!(
+                "router[{}] output[{}] not found string field[{}]",
+                self.router,
+                out,
+                self.route_field
+            )
+            .err(),
+        }
+    }
+    fn node_entity(&self, name: &str) -> anyhow::Result<ServiceEntity> {
+        match self.node_set.get(name) {
+            Some(s) => Ok(s.clone().into()),
+            None => anyhow::anyhow!("node[{}] not found", name).err(),
+        }
+    }
+}
+
+impl Plan for DynamicPlan {
+    fn show_plan(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or("{}".into())
+    }
+
+    fn start_node_name(&self) -> &str {
+        self.start.as_str()
+    }
+
+    fn end_node_name(&self) -> &str {
+        self.end.as_str()
+    }
+
+    fn get(&mut self, name: &str) -> Option<ServiceEntity> {
+        self.node_set.get(name).map(|x| x.clone().into())
+    }
+
+    fn next(&mut self, ctx: Ctx, name: &str) -> anyhow::Result<NextPlan> {
+        if name == self.end {
+            return Ok(NextPlan::End);
+        }
+        if name != self.router {
+            return Ok(NextPlan::Nodes(vec![
+                self.node_entity(self.router.as_str())?
+            ]));
+        }
+        self.steps += 1;
+        if self.max_steps > 0 && self.steps > self.max_steps {
+            return anyhow::anyhow!(
+                "router[{}] step budget[{}] exhausted",
+                self.router,
+                self.max_steps
+            )
+            .err();
+        }
+        let next = self.route(&ctx)?;
+        if next != self.end && !self.allow.contains(&next) {
+            return anyhow::anyhow!(
+                "router[{}] chose node[{}] which is not allowed",
+                self.router,
+                next
+            )
+            .err();
+        }
+        Ok(NextPlan::Nodes(vec![self.node_entity(next.as_str())?]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{Ctx, CtxSerdeExt, EngineRT, ServiceEntity};
+    use crate::plan::dynamic::DynamicPlan;
+    use crate::service::ext::{Obj, ServiceLoaderWrap};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_dynamic_plan() {
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service(
+                        "router",
+                        |_ctx: Ctx, input: Obj, _se: ServiceEntity| async move {
+                            let input = Value::from(input);
+                            //模拟llm：没有搜索结果时先搜索，否则结束
+                            let next = match input.get("found") {
+                                Some(_) => input.get("done").cloned().unwrap_or("end".into()),
+                                None => "tool_search".into(),
+                            };
+                            Ok(json!({ "next": next }))
+                        },
+                    )
+                    .register_json_ext_service(
+                        "search",
+                        |_ctx: Ctx, input: Obj, _se: ServiceEntity| async move {
+                            let input = Value::from(input);
+                            Ok(json!({"result": format!("search:{}", input["query"].as_str().unwrap_or(""))}))
+                        },
+                    ),
+            )
+            .build();
+
+        let plan = DynamicPlan::new("router")
+            .nodes([
+                ("start", r#"{"service_name":"start"}"#),
+                ("router", r#"{"service_name":"router","config":{"none_quote_skip":true,"default_json":{"found":"${{tool_search.result}}","done":"${{start.done}}"}}}"#),
+                ("tool_search", r#"{"service_name":"search","config":{"default_json":{"query":"${{start.query}}"}}}"#),
+                ("tool_calc", r#"{"service_name":"search","config":{"default_json":{"query":"${{start.query}}"}}}"#),
+                ("end", r#"{"service_name":"end","config":{"transform_rule":{"answer":{"quote":"tool_search.result"}}}}"#),
+            ])
+            .allow(["tool_search", "tool_calc"])
+            .set_max_steps(3)
+            .check()
+            .unwrap();
+
+        let res: Value = rt
+            .ctx(plan.clone())
+            .serde_run(json!({"query":"art"}))
+            .await
+            .unwrap();
+        assert_eq!(res["answer"], "search:art");
+
+        //router一直选择搜索，超出步数限制
+        let err = rt
+            .ctx(plan.clone())
+            .serde_run::<_, Value>(json!({"query":"art","done":"tool_search"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exhausted"), "{}", err);
+
+        //router选择了不允许的节点
+        let err = rt
+            .ctx(plan)
+            .serde_run::<_, Value>(json!({"query":"art","done":"router"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not allowed"), "{}", err);
+    }
+}
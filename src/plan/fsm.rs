@@ -0,0 +1,226 @@
+use crate::core::{Ctx, NextPlan, Plan, ServiceEntity, ServiceEntityJson};
+use crate::service::flow::SelectNode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wd_tools::PFErr;
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transition {
+    pub to: String,
+    pub when: SelectNode,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub service: ServiceEntityJson,
+    pub transitions: Vec<Transition>,
+}
+
+// 状态机：状态可以重复进入，到达任意终止状态后结束，结果取终止状态的输出
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateMachine {
+    pub start: String,
+    pub terminals: Vec<String>,
+    pub states: HashMap<String, State>,
+    pub max_steps: usize,
+    pub steps: usize,
+    pub finished: String,
+}
+
+impl StateMachine {
+    pub fn new<S: Into<String>>(start: S) -> Self {
+        Self {
+            start: start.into(),
+            max_steps: 100,
+            ..Default::default()
+        }
+    }
+    pub fn state<N: Into<String>, E: Into<ServiceEntityJson>>(
+        mut self,
+        (name, se): (N, E),
+    ) -> Self {
+        let name = name.into();
+        let service = se.into().set_node_name(name.clone());
+        let state = self.states.entry(name).or_default();
+        state.service = service;
+        self
+    }
+    pub fn states<N: Into<String>, E: Into<ServiceEntityJson>, I: IntoIterator<Item = (N, E)>>(
+        mut self,
+        states: I,
+    ) -> Self {
+        for i in states {
+            self = self.state(i);
+        }
+        self
+    }
+    //按添加顺序匹配，第一个满足条件的转移生效
+    pub fn transition<F: Into<String>, T: Into<String>>(
+        mut self,
+        from: F,
+        to: T,
+        when: SelectNode,
+    ) -> Self {
+        let state = self.states.entry(from.into()).or_default();
+        state.transitions.push(Transition {
+            to: to.into(),
+            when,
+        });
+        self
+    }
+    pub fn terminal<S: Into<String>>(mut self, name: S) -> Self {
+        let name = name.into();
+        if !self.terminals.contains(&name) {
+            self.terminals.push(name);
+        }
+        self
+    }
+    pub fn set_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+    pub fn check(self) -> anyhow::Result<Self> {
+        if !self.states.contains_key(self.start.as_str()) {
+            return anyhow::anyhow!("not found start state[{}]", self.start).err();
+        }
+        if self.terminals.is_empty() {
+            return anyhow::anyhow!("state machine must have a terminal state").err();
+        }
+        for i in self.terminals.iter() {
+            if !self.states.contains_key(i) {
+                return anyhow::anyhow!("not found terminal state[{}]", i).err();
+            }
+        }
+        for (k, v) in self.states.iter() {
+            if v.service.service_name.is_empty() {
+                return anyhow::anyhow!("state[{}].service.name is empty", k).err();
+            }
+            if v.transitions.is_empty() && !self.terminals.contains(k) {
+                return anyhow::anyhow!("state[{}] has no transition and is not terminal", k).err();
+            }
+            for t in v.transitions.iter() {
+                if !self.states.contains_key(t.to.as_str()) {
+                    return anyhow::anyhow!("state[{}] -> state[{}] not found", k, t.to).err();
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl Plan for StateMachine {
+    fn show_plan(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or("{}".into())
+    }
+
+    fn start_node_name(&self) -> &str {
+        self.start.as_str()
+    }
+
+    fn end_node_name(&self) -> &str {
+        if !self.finished.is_empty() {
+            return self.finished.as_str();
+        }
+        self.terminals.first().map(|x| x.as_str()).unwrap_or("")
+    }
+
+    fn get(&mut self, name: &str) -> Option<ServiceEntity> {
+        self.states.get(name).map(|x| x.service.clone().into())
+    }
+
+    fn next(&mut self, ctx: Ctx, name: &str) -> anyhow::Result<NextPlan> {
+        if self.terminals.iter().any(|x| x == name) {
+            self.finished = name.to_string();
+            return Ok(NextPlan::End);
+        }
+        self.steps += 1;
+        if self.max_steps > 0 && self.steps > self.max_steps {
+            return anyhow::anyhow!("state machine max steps[{}] exceeded", self.max_steps).err();
+        }
+        let state = match self.states.get(name) {
+            Some(s) => s,
+            None => return anyhow::anyhow!("state[{}] not found", name).err(),
+        };
+        let lookup = |p: &str| ctx.deref_var_path(p);
+        for t in state.transitions.iter() {
+            let cond = t.when.resolve(&lookup)?;
+            if !cond.generate_result()? {
+                continue;
+            }
+            return match self.states.get(t.to.as_str()) {
+                Some(s) => Ok(NextPlan::Nodes(vec![s.service.clone().into()])),
+                None => anyhow::anyhow!("state[{}] not found", t.to).err(),
+            };
+        }
+        anyhow::anyhow!("state[{}] no transition matched", name).err()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{CtxSerdeExt, EngineRT};
+    use crate::plan::fsm::StateMachine;
+    use crate::service::ext::ServiceLoaderWrap;
+    use crate::service::flow::SelectNode;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_state_machine() {
+        #[derive(Default, Debug, Clone, Serialize, Deserialize)]
+        #[serde(default)]
+        struct Counter {
+            n: isize,
+        }
+        let rt = EngineRT::default()
+            .set_service_loader(ServiceLoaderWrap::default().register_json_ext_service(
+                "inc",
+                |_ctx, mut c: Counter, _se| async move {
+                    c.n += 1;
+                    Ok(c)
+                },
+            ))
+            .build();
+
+        //start -> count -> count -> ... -> done
+        //      -> reject
+        let fsm = StateMachine::new("start")
+            .states([
+                ("start", r#"{"service_name":"start"}"#),
+                ("count", r#"{"service_name":"inc","config":{"none_quote_skip":true,"transform_rule":{"n":{"quote":"count.n"}}}}"#),
+                ("done", r#"{"service_name":"end","config":{"transform_rule":{"n":{"quote":"count.n"}}}}"#),
+                ("reject", r#"{"service_name":"end","config":{"transform_rule":{"reason":{"value":"rejected"}}}}"#),
+            ])
+            .transition("start", "reject", SelectNode::Equal("${{start.confirm}}".into(), false.into()))
+            .transition("start", "count", SelectNode::None)
+            .transition("count", "count", SelectNode::Less("${{count.n}}".into(), "${{start.times}}".into()))
+            .transition("count", "done", SelectNode::None)
+            .terminal("done")
+            .terminal("reject")
+            .check()
+            .unwrap();
+
+        let res: Value = rt
+            .ctx(fsm.clone())
+            .serde_run(json!({"confirm":true,"times":3}))
+            .await
+            .unwrap();
+        assert_eq!(res["n"], 3);
+
+        let res: Value = rt
+            .ctx(fsm.clone())
+            .serde_run(json!({"confirm":false,"times":3}))
+            .await
+            .unwrap();
+        assert_eq!(res["reason"], "rejected");
+
+        let res = rt
+            .ctx(fsm.set_max_steps(5))
+            .serde_run::<_, Value>(json!({"confirm":true,"times":10}))
+            .await;
+        assert!(res.is_err());
+    }
+}
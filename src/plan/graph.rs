@@ -1,6 +1,6 @@
 use crate::core::{Ctx, JsonInput, NextPlan, Plan, ServiceEntity, ServiceEntityJson};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use wd_tools::PFErr;
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -132,6 +132,13 @@ pub struct Graph {
     pub start: String,
     pub end: String,
     pub node_set: HashMap<String, GraphNode>,
+    #[serde(skip)]
+    scheduled: HashSet<String>,
+    #[serde(skip)]
+    completed: HashSet<String>,
+    //移除前置节点后已经就绪的汇合节点，随下一次next调度
+    #[serde(skip)]
+    ready: Vec<String>,
 }
 
 impl Graph {
@@ -223,9 +230,9 @@ impl Graph {
         for (n, i) in self.node_set.iter() {
             for e in i.from.iter() {
                 if let Some(s) = self.node_set.get(e) {
-                    if !s.have_to(e) {
+                    if !s.have_to(n) {
                         return anyhow::anyhow!(
-                            "node[{n}] prerequisite requirements [{e}], but node[{e}] no to [n]"
+                            "node[{n}] prerequisite requirements [{e}], but node[{e}] no to [{n}]"
                         )
                         .err();
                     }
@@ -280,10 +287,13 @@ impl Plan for Graph {
     }
 
     fn get(&mut self, name: &str) -> Option<ServiceEntity> {
-        self.get_service_entity(name).map(|x| x.into())
+        let se = self.get_service_entity(name)?;
+        self.scheduled.insert(name.to_string());
+        Some(se.into())
     }
 
     fn next(&mut self, _ctx: Ctx, name: &str) -> anyhow::Result<NextPlan> {
+        self.completed.insert(name.to_string());
         if name == self.end {
             return Ok(NextPlan::End);
         }
@@ -295,13 +305,23 @@ impl Plan for Graph {
         let mut next = vec![];
         for i in to {
             if let Some(n) = self.node_set.get_mut(i.as_str()) {
-                if let Some(s) = n.from_completed(i.as_str()) {
+                if let Some(s) = n.from_completed(name) {
+                    self.scheduled.insert(i);
                     next.push(s.into());
                 }
             } else {
                 return anyhow::anyhow!("node[{}] not found", i).err();
             }
         }
+        for i in std::mem::take(&mut self.ready) {
+            if self.scheduled.contains(i.as_str()) {
+                continue;
+            }
+            if let Some(n) = self.node_set.get_mut(i.as_str()) {
+                next.push(n.get_service_entity().into());
+                self.scheduled.insert(i);
+            }
+        }
         Ok(NextPlan::Nodes(next))
     }
 
@@ -310,15 +330,81 @@ impl Plan for Graph {
             s.to = to;
         }
     }
+
+    fn add_node(&mut self, node: ServiceEntityJson) -> anyhow::Result<()> {
+        let name = node.node_name.clone();
+        if name.is_empty() {
+            return anyhow::anyhow!("add node failed: node_name is empty").err();
+        }
+        if self.node_set.contains_key(name.as_str()) {
+            return anyhow::anyhow!("add node failed: node[{}] already exists", name).err();
+        }
+        let node = GraphNode::new(name.clone()).set_service_entity(node);
+        self.node_set.insert(name, node);
+        Ok(())
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str) -> anyhow::Result<()> {
+        if !self.node_set.contains_key(from) {
+            return anyhow::anyhow!("add edge failed: node[{}] not found", from).err();
+        }
+        //已经完成的节点不会再向下调度，新边永远不会被执行
+        if self.completed.contains(from) {
+            return anyhow::anyhow!("add edge failed: node[{}] already completed", from).err();
+        }
+        //已经调度的节点不能再增加前置节点
+        if self.scheduled.contains(to) {
+            return anyhow::anyhow!("add edge failed: node[{}] already scheduled", to).err();
+        }
+        if let Some(n) = self.node_set.get_mut(to) {
+            //声明了from的节点需要等待所有前置节点，新的前置节点也要计入
+            if !n.from.is_empty() {
+                if !n.from_completed.is_empty() && !n.from.iter().any(|x| x == from) {
+                    n.from_completed.push(from.to_string());
+                }
+                n.add_from(from);
+            }
+        } else {
+            return anyhow::anyhow!("add edge failed: node[{}] not found", to).err();
+        }
+        if let Some(n) = self.node_set.get_mut(from) {
+            n.add_to(to);
+        }
+        Ok(())
+    }
+
+    fn remove_pending_node(&mut self, name: &str) -> anyhow::Result<()> {
+        if name == self.start || name == self.end {
+            return anyhow::anyhow!("remove node failed: node[{}] is start or end", name).err();
+        }
+        if self.scheduled.contains(name) {
+            return anyhow::anyhow!("remove node failed: node[{}] already scheduled", name).err();
+        }
+        if self.node_set.remove(name).is_none() {
+            return anyhow::anyhow!("remove node failed: node[{}] not found", name).err();
+        }
+        for n in self.node_set.values_mut() {
+            n.to.retain(|x| x != name);
+            n.from.retain(|x| x != name);
+            //汇合节点已经开始计数时，剩余的前置节点可能都已完成
+            if !n.from_completed.is_empty() {
+                n.from_completed.retain(|x| x != name);
+                if n.from_completed.is_empty() {
+                    self.ready.push(n.node_name.clone());
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::core::{CtxSerdeExt, EngineRT, JsonInput, Plan};
+    use crate::core::{Ctx, CtxSerdeExt, EngineRT, JsonInput, Plan, ServiceEntity, ServiceEntityJson};
     use crate::plan::graph::{Graph, GraphNode};
     use crate::service::ext::ServiceLoaderWrap;
     use serde::{Deserialize, Serialize};
-    use serde_json::json;
+    use serde_json::{json, Value};
 
     #[test]
     fn test_graph() {
@@ -350,6 +436,28 @@ mod test {
         println!("success");
     }
 
+    #[test]
+    fn test_graph_check_from() {
+        let graph = Graph::default()
+            .nodes([
+                ("start", r#"{"service_name":"start"}"#),
+                ("A", r#"{"service_name":"a"}"#),
+                ("B", r#"{"service_name":"b"}"#),
+                ("end", r#"{"service_name":"end"}"#),
+            ])
+            .edges([("start", "A"), ("start", "B"), ("A", "end"), ("B", "end")]);
+        graph.clone().check().expect("from matches to");
+
+        //end依赖start，但start没有指向end
+        let mut graph = graph;
+        graph.node_set.get_mut("end").unwrap().from.push("start".into());
+        let err = graph.check().unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "node[end] prerequisite requirements [start], but node[start] no to [end]"
+        );
+    }
+
     #[tokio::test]
     async fn test_select_graph() {
         #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -418,4 +526,120 @@ mod test {
             .unwrap();
         assert_eq!(res.result, 9);
     }
+
+    #[tokio::test]
+    async fn test_plan_mutation() {
+        #[derive(Default, Debug, Clone, Serialize, Deserialize)]
+        #[serde(default)]
+        struct AddInOut {
+            a: isize,
+            b: isize,
+            result: isize,
+        }
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("add", |_ctx, mut io: AddInOut, _se| async move {
+                        io.result = io.a + io.b;
+                        Ok(io)
+                    })
+                    .register_json_ext_service(
+                        "planner",
+                        |ctx: Ctx, _input: Value, se: ServiceEntity| async move {
+                            //根据"llm"的规划，扩展出两个工具节点
+                            ctx.deref_mut_plan(|p| {
+                                p.set_to(se.node_name.as_str(), vec![]);
+                                p.remove_pending_node("placeholder")?;
+                                p.add_node(ServiceEntityJson::from(r#"{"service_name":"add","node_name":"tool_1","config":{"transform_rule":{"a":{"quote":"start.number"},"b":{"value":1}}}}"#))?;
+                                p.add_node(ServiceEntityJson::from(r#"{"service_name":"add","node_name":"tool_2","config":{"transform_rule":{"a":{"quote":"tool_1.result"},"b":{"value":10}}}}"#))?;
+                                p.add_edge(se.node_name.as_str(), "tool_1")?;
+                                p.add_edge("tool_1", "tool_2")?;
+                                p.add_edge("tool_2", "end")
+                            })?;
+                            Ok(Value::Null)
+                        },
+                    ),
+            )
+            .build();
+
+        let plan = Graph::default()
+            .node(("start",r#"{"service_name":"start","config":{"transform_rule":{"number":{"quote":"number"}}}}"#))
+            .node(GraphNode::new("planner").set_service_entity_json("planner",JsonInput::default()))
+            .node(GraphNode::new("placeholder").set_service_entity_json("add",JsonInput::default()))
+            .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"result":{"quote":"tool_2.result"}}}}"#))
+            .edges([("start","planner"),("planner","placeholder"),("placeholder","end")])
+            .check()
+            .unwrap();
+
+        let mut check = plan.clone();
+        assert!(check.remove_pending_node("start").is_err());
+        assert!(check.add_edge("start", "not_exist").is_err());
+        //start已经执行完成，不能再从start增加边
+        check.get("start");
+        check.next(Ctx::new(EngineRT::default().build(), ()), "start").unwrap();
+        assert!(check.add_edge("start", "end").is_err());
+        assert!(check.add_edge("planner", "end").is_ok());
+
+        let res: AddInOut = rt
+            .ctx(plan)
+            .serde_run(json!({
+                "number":8,
+            }))
+            .await
+            .unwrap();
+        assert_eq!(res.result, 19);
+    }
+
+    #[tokio::test]
+    async fn test_remove_join_branch() {
+        #[derive(Default, Debug, Clone, Serialize, Deserialize)]
+        #[serde(default)]
+        struct AddInOut {
+            a: isize,
+            b: isize,
+            result: isize,
+        }
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("add", |_ctx, mut io: AddInOut, _se| async move {
+                        io.result = io.a + io.b;
+                        Ok(io)
+                    })
+                    .register_json_ext_service("remover", |ctx: Ctx, _input: Value, _se| async move {
+                        //等待另一条分支先完成，使join开始计数
+                        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                        ctx.deref_mut_plan(|p| p.remove_pending_node("B"))?;
+                        Ok(Value::Null)
+                    }),
+            )
+            .build();
+
+        let join = r#"{"service_name":"add","config":{"transform_rule":{"a":{"quote":"A.result"},"b":{"value":1}}}}"#;
+        let plan = Graph::default()
+            .node(("start",r#"{"service_name":"start","config":{"transform_rule":{"number":{"quote":"number"}}}}"#))
+            .node(("A",r#"{"service_name":"add","config":{"transform_rule":{"a":{"quote":"start.number"},"b":{"value":1}}}}"#))
+            .node(GraphNode::new("X").set_service_entity_json("remover",JsonInput::default()))
+            .node(GraphNode::new("B").set_service_entity_json("add",JsonInput::default()))
+            .node(GraphNode::from(("join", join)).set_from(vec!["A", "B"]))
+            .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"result":{"quote":"join.result"}}}}"#))
+            .edges([("start","A"),("start","X"),("X","B"),("A","join"),("B","join"),("join","end")])
+            .check()
+            .unwrap();
+
+        let mut check = plan.clone();
+        check.get("start");
+        assert!(check.add_edge("A", "start").is_err());
+
+        let res: AddInOut = tokio::time::timeout(
+            std::time::Duration::from_secs(3),
+            rt.ctx(plan).serde_run(json!({
+                "number":8,
+            })),
+        )
+        .await
+        .expect("join never ran")
+        .unwrap();
+        assert_eq!(res.result, 10);
+    }
 }
@@ -0,0 +1,325 @@
+use crate::core::{ServiceEntityJson, ServiceLoader};
+use crate::plan::dag::DAG;
+use crate::plan::graph::Graph;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintLevel {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintIssue {
+    pub level: LintLevel,
+    pub node: String,
+    pub message: String,
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let level = match self.level {
+            LintLevel::Warning => "warning",
+            LintLevel::Error => "error",
+        };
+        write!(f, "[{}] node[{}]: {}", level, self.node, self.message)
+    }
+}
+
+struct LintNode<'a> {
+    name: &'a str,
+    from: &'a [String],
+    to: &'a [String],
+    service: Option<&'a ServiceEntityJson>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Lint {
+    pub issues: Vec<LintIssue>,
+    // (node,service)，等待check_services确认是否注册
+    #[serde(skip)]
+    services: Vec<(String, String)>,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for i in self.issues.iter() {
+            writeln!(f, "{}", i)?;
+        }
+        Ok(())
+    }
+}
+
+impl Lint {
+    pub fn graph(g: &Graph) -> Self {
+        let nodes = g
+            .node_set
+            .iter()
+            .map(|(k, v)| LintNode {
+                name: k.as_str(),
+                from: v.from.as_slice(),
+                to: v.to.as_slice(),
+                service: Some(&v.service),
+            })
+            .collect::<Vec<_>>();
+        Self::check(g.start.as_str(), g.end.as_str(), nodes)
+    }
+    pub fn dag(d: &DAG) -> Self {
+        let nodes = d
+            .node_set
+            .iter()
+            .map(|(k, v)| LintNode {
+                name: k.as_str(),
+                from: v.from.as_slice(),
+                to: v.to.as_slice(),
+                service: v.service.as_ref(),
+            })
+            .collect::<Vec<_>>();
+        Self::check(d.start.as_str(), d.end.as_str(), nodes)
+    }
+    pub fn errors(&self) -> Vec<&LintIssue> {
+        self.issues
+            .iter()
+            .filter(|x| x.level == LintLevel::Error)
+            .collect()
+    }
+    pub fn warnings(&self) -> Vec<&LintIssue> {
+        self.issues
+            .iter()
+            .filter(|x| x.level == LintLevel::Warning)
+            .collect()
+    }
+    pub fn has_error(&self) -> bool {
+        self.issues.iter().any(|x| x.level == LintLevel::Error)
+    }
+    pub fn into_result(self) -> anyhow::Result<Self> {
+        if self.has_error() {
+            return Err(anyhow::anyhow!("plan lint failed:\n{}", self));
+        }
+        Ok(self)
+    }
+    fn push<N: Into<String>, M: Into<String>>(&mut self, level: LintLevel, node: N, message: M) {
+        self.issues.push(LintIssue {
+            level,
+            node: node.into(),
+            message: message.into(),
+        })
+    }
+    //引用路径的第一段为节点名
+    fn quote_node(path: &str) -> &str {
+        let end = path.find(['.', '[']).unwrap_or(path.len());
+        &path[..end]
+    }
+    fn check(start: &str, end: &str, mut nodes: Vec<LintNode>) -> Self {
+        nodes.sort_by(|a, b| a.name.cmp(b.name));
+        let mut lint = Lint::default();
+        let names = nodes.iter().map(|x| x.name).collect::<HashSet<_>>();
+        if !names.contains(start) {
+            lint.push(LintLevel::Error, start, "start node not found");
+        }
+        if !names.contains(end) {
+            lint.push(LintLevel::Error, end, "end node not found");
+        }
+        //重复边与悬空边
+        let mut parents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for n in nodes.iter() {
+            let mut seen = HashSet::new();
+            for t in n.to.iter() {
+                if !seen.insert(t.as_str()) {
+                    lint.push(
+                        LintLevel::Error,
+                        n.name,
+                        format!("duplicate edge[{} -> {}]", n.name, t),
+                    );
+                    continue;
+                }
+                if !names.contains(t.as_str()) {
+                    lint.push(
+                        LintLevel::Error,
+                        n.name,
+                        format!("edge[{} -> {}] target not found", n.name, t),
+                    );
+                    continue;
+                }
+                parents.entry(t.as_str()).or_default().push(n.name);
+            }
+            let mut seen = HashSet::new();
+            for f in n.from.iter() {
+                if !seen.insert(f.as_str()) {
+                    lint.push(
+                        LintLevel::Error,
+                        n.name,
+                        format!("duplicate edge[{} <- {}]", n.name, f),
+                    );
+                }
+            }
+        }
+        //从start出发的可达性
+        let mut reachable = HashSet::new();
+        let mut queue = VecDeque::from([start]);
+        let children = nodes
+            .iter()
+            .map(|x| (x.name, x.to))
+            .collect::<HashMap<_, _>>();
+        while let Some(n) = queue.pop_front() {
+            if !reachable.insert(n) {
+                continue;
+            }
+            if let Some(to) = children.get(n) {
+                queue.extend(to.iter().map(|x| x.as_str()));
+            }
+        }
+        for n in nodes.iter() {
+            if !reachable.contains(n.name) {
+                lint.push(
+                    LintLevel::Warning,
+                    n.name,
+                    format!("unreachable from start node[{}]", start),
+                );
+            }
+        }
+        //引用检查：被引用节点必须是祖先节点，否则运行时取不到变量
+        let mut quoted = HashSet::new();
+        for n in nodes.iter() {
+            let se = match n.service {
+                Some(s) => s,
+                None => {
+                    lint.push(LintLevel::Error, n.name, "service is empty");
+                    continue;
+                }
+            };
+            lint.services
+                .push((n.name.to_string(), se.service_name.clone()));
+            if matches!(se.service_name.as_str(), "batch" | "flow_reduce") {
+                if let Some(serde_json::Value::String(s)) = se
+                    .config
+                    .literal_value("service")
+                    .filter(|x| x != "")
+                {
+                    lint.services.push((n.name.to_string(), s));
+                }
+            }
+            //start节点的引用指向运行输入
+            if n.name == start {
+                continue;
+            }
+            let ancestors = Self::ancestors(n.name, &parents);
+            let level = if se.config.is_none_quote_skip() {
+                LintLevel::Warning
+            } else {
+                LintLevel::Error
+            };
+            for path in se.config.quote_paths() {
+                let q = Self::quote_node(path.as_str());
+                quoted.insert(q.to_string());
+                if !names.contains(q) {
+                    lint.push(
+                        level,
+                        n.name,
+                        format!("quote[{}] references unknown node[{}]", path, q),
+                    );
+                } else if !ancestors.contains(q) {
+                    lint.push(
+                        level,
+                        n.name,
+                        format!(
+                            "quote[{}] references node[{}] which is not an ancestor",
+                            path, q
+                        ),
+                    );
+                }
+            }
+        }
+        for n in nodes.iter() {
+            if n.name == end || quoted.contains(n.name) {
+                continue;
+            }
+            //flow_select通过修改plan生效，输出无需引用
+            if n.service.map(|x| x.service_name == "flow_select") == Some(true) {
+                continue;
+            }
+            lint.push(LintLevel::Warning, n.name, "output is never quoted");
+        }
+        lint
+    }
+    fn ancestors<'a>(node: &'a str, parents: &HashMap<&'a str, Vec<&'a str>>) -> HashSet<&'a str> {
+        let mut set = HashSet::new();
+        let mut queue = VecDeque::from([node]);
+        while let Some(n) = queue.pop_front() {
+            if let Some(ps) = parents.get(n) {
+                for p in ps {
+                    if set.insert(*p) {
+                        queue.push_back(*p);
+                    }
+                }
+            }
+        }
+        set
+    }
+    pub async fn check_services<L: ServiceLoader + Sync + ?Sized>(mut self, loader: &L) -> Self {
+        let services = std::mem::take(&mut self.services);
+        for (node, name) in services {
+            if loader.load(name.as_str()).await.is_none() {
+                self.push(
+                    LintLevel::Error,
+                    node,
+                    format!("service[{}] is not registered", name),
+                );
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::JsonInput;
+    use crate::plan::graph::{Graph, GraphNode};
+    use crate::plan::lint::{Lint, LintLevel};
+    use crate::service::ext::ServiceLoaderWrap;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_lint_graph() {
+        let graph = Graph::default()
+            .node(("start", r#"{"service_name":"start","config":{"transform_rule":{"query":{"quote":"query"}}}}"#))
+            .node(("a", r#"{"service_name":"start","config":{"transform_rule":{"query":{"quote":"start.query"},"b":{"quote":"b.answer"}}}}"#))
+            .node(("b", r#"{"service_name":"start","config":{"transform_rule":{"query":{"quote":"start.query"}}}}"#))
+            .node(GraphNode::new("batch").set_service_entity_json("batch",JsonInput::default().set_default_json(json!({"service":"not_exist","inputs":"${{a.list}}"}))))
+            .node(("lost", r#"{"service_name":"end"}"#))
+            .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"a":{"quote":"a"},"batch":{"quote":"batch.outputs"}}}}"#))
+            .edges([("start", "a"), ("start", "b"), ("a", "batch"), ("b", "batch"), ("batch", "end")]);
+        let mut graph = graph;
+        graph.node_set.get_mut("b").unwrap().to.push("batch".into());
+
+        let lint = Lint::graph(&graph)
+            .check_services(&ServiceLoaderWrap::default())
+            .await;
+
+        let errors = lint.errors();
+        assert!(errors
+            .iter()
+            .any(|x| x.node == "a" && x.message.contains("b.answer")));
+        assert!(errors
+            .iter()
+            .any(|x| x.node == "b" && x.message.contains("duplicate edge")));
+        assert!(errors
+            .iter()
+            .any(|x| x.node == "batch" && x.message.contains("not_exist")));
+        let warnings = lint.warnings();
+        assert!(warnings
+            .iter()
+            .any(|x| x.node == "lost" && x.message.contains("unreachable")));
+        assert!(warnings
+            .iter()
+            .any(|x| x.node == "lost" && x.message.contains("never quoted")));
+        assert!(lint
+            .issues
+            .iter()
+            .all(|x| x.node != "end" || x.level != LintLevel::Error));
+        assert!(lint.into_result().is_err());
+    }
+}
@@ -1,2 +1,6 @@
 pub mod dag;
+pub mod dynamic;
+pub mod fsm;
 pub mod graph;
+pub mod lint;
+pub mod pipeline;
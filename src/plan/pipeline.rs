@@ -0,0 +1,191 @@
+use crate::core::{Ctx, JsonInput, NextPlan, Plan, ServiceEntity, ServiceEntityJson};
+use serde::{Deserialize, Serialize};
+use wd_tools::PFErr;
+
+// 线性流程：start -> step_0 -> step_1 -> ...，结果取最后一个step的输出
+// 每个step以上一个节点的输出作为输入的基础值，其他规则在此基础上覆盖字段；自行配置了"*"规则的step除外
+// 上一步输出不是对象时，只有没有配置其他规则的step才会把它整体作为输入
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pipeline {
+    pub start: String,
+    pub steps: Vec<ServiceEntityJson>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self {
+            start: "start".into(),
+            steps: vec![],
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn set_start_node_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.start = name.into();
+        self
+    }
+    //未指定node_name时使用service_name，重名时追加序号
+    pub fn step<E: Into<ServiceEntityJson>>(mut self, step: E) -> Self {
+        let mut step = step.into();
+        if step.node_name.is_empty() {
+            let mut name = step.service_name.clone();
+            if self.position(name.as_str()).is_some() {
+                name = format!("{}_{}", step.service_name, self.steps.len());
+            }
+            step.node_name = name;
+        }
+        self.steps.push(step);
+        self
+    }
+    pub fn steps<E: Into<ServiceEntityJson>, I: IntoIterator<Item = E>>(
+        mut self,
+        steps: I,
+    ) -> Self {
+        for i in steps {
+            self = self.step(i);
+        }
+        self
+    }
+    pub fn check(self) -> anyhow::Result<Self> {
+        if self.steps.is_empty() {
+            return anyhow::anyhow!("pipeline steps is empty").err();
+        }
+        for (i, s) in self.steps.iter().enumerate() {
+            if s.service_name.is_empty() {
+                return anyhow::anyhow!("pipeline step[{}].service.name is empty", i).err();
+            }
+            if s.node_name == self.start {
+                return anyhow::anyhow!(
+                    "pipeline step[{}] node_name[{}] conflicts with start",
+                    i,
+                    s.node_name
+                )
+                .err();
+            }
+            if self.position(s.node_name.as_str()) != Some(i) {
+                return anyhow::anyhow!("pipeline step node_name[{}] repeated", s.node_name).err();
+            }
+        }
+        Ok(self)
+    }
+    fn position(&self, name: &str) -> Option<usize> {
+        self.steps.iter().position(|x| x.node_name == name)
+    }
+    fn step_entity(&self, index: usize) -> ServiceEntity {
+        let mut se = self.steps[index].clone();
+        let prev = if index == 0 {
+            self.start.as_str()
+        } else {
+            self.steps[index - 1].node_name.as_str()
+        };
+        se.config = se.config.set_prev_base(prev);
+        se.into()
+    }
+}
+
+impl Plan for Pipeline {
+    fn show_plan(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or("{}".into())
+    }
+
+    fn start_node_name(&self) -> &str {
+        self.start.as_str()
+    }
+
+    fn end_node_name(&self) -> &str {
+        match self.steps.last() {
+            Some(s) => s.node_name.as_str(),
+            None => self.start.as_str(),
+        }
+    }
+
+    fn get(&mut self, name: &str) -> Option<ServiceEntity> {
+        if name == self.start {
+            let se = ServiceEntityJson::default()
+                .set_service_name("start")
+                .set_node_name(name)
+                .set_config(JsonInput::default());
+            return Some(se.into());
+        }
+        self.position(name).map(|i| self.step_entity(i))
+    }
+
+    fn next(&mut self, _ctx: Ctx, name: &str) -> anyhow::Result<NextPlan> {
+        let index = if name == self.start {
+            0
+        } else if let Some(i) = self.position(name) {
+            i + 1
+        } else {
+            return anyhow::anyhow!("pipeline node[{}] not found", name).err();
+        };
+        if index >= self.steps.len() {
+            return Ok(NextPlan::End);
+        }
+        Ok(NextPlan::Nodes(vec![self.step_entity(index)]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{CtxSerdeExt, EngineRT, JsonInput};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::ServiceLoaderWrap;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_pipeline() {
+        #[derive(Default, Debug, Clone, Serialize, Deserialize)]
+        #[serde(default)]
+        struct Number {
+            n: isize,
+            step: isize,
+        }
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("inc", |_ctx, mut c: Number, _se| async move {
+                        c.n += 1;
+                        c.step += 1;
+                        Ok(c)
+                    })
+                    .register_json_ext_service("square", |_ctx, mut c: Number, _se| async move {
+                        c.n *= c.n;
+                        c.step += 1;
+                        Ok(c)
+                    }),
+            )
+            .build();
+
+        // {n:1} -> inc -> inc -> square -> square(以上一步为基础，n取第一个inc的输出)
+        let plan = Pipeline::default()
+            .steps([("inc", JsonInput::default()), ("inc", JsonInput::default())])
+            .step(("square", JsonInput::default()))
+            .step((
+                "square",
+                JsonInput::default().add_transform_quote("n", "inc.n"),
+            ))
+            .check()
+            .unwrap();
+        assert_eq!(plan.steps[1].node_name, "inc_1");
+
+        let res: Number = rt
+            .ctx(plan.clone())
+            .serde_run(json!({"n":1}))
+            .await
+            .unwrap();
+        assert_eq!(res.n, 4);
+        assert_eq!(res.step, 4);
+
+        //自行配置"*"的step不再以上一步为基础
+        let plan = plan.step((
+            "inc",
+            JsonInput::default().add_transform_quote("*", "start"),
+        ));
+        let res: Number = rt.ctx(plan).serde_run(json!({"n":1})).await.unwrap();
+        assert_eq!(res.n, 2);
+        assert_eq!(res.step, 1);
+    }
+}
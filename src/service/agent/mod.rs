@@ -1,5 +1,7 @@
 mod fixed_plan;
+mod react;
 mod workflow;
 
 pub use fixed_plan::*;
+pub use react::*;
 pub use workflow::*;
@@ -0,0 +1,336 @@
+use crate::core::{Ctx, Error, JsonInput, JsonServiceExt, ServiceEntity};
+use crate::service::ext::Obj;
+use crate::service::llm::{ChatMessage, ToolCall, ToolDefinition};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use wd_tools::PFErr;
+
+pub const REACT_DEFAULT_MAX_STEPS: usize = 8;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ReactAgentCfg {
+    //模型服务，输入输出与chat_completion一致
+    pub model: String,
+    //透传给模型服务的其他参数，如temperature
+    pub model_args: Obj,
+    pub system: String,
+    pub prompt: String,
+    //历史消息，放在system之后、prompt之前
+    pub messages: Vec<ChatMessage>,
+    //可调用的服务名，参数schema取自服务的input_schema
+    pub tools: Vec<String>,
+    pub tool_descriptions: HashMap<String, String>,
+    pub max_steps: usize,
+}
+
+impl Default for ReactAgentCfg {
+    fn default() -> Self {
+        Self {
+            model: "chat_completion".into(),
+            model_args: Obj::default(),
+            system: String::new(),
+            prompt: String::new(),
+            messages: vec![],
+            tools: vec![],
+            tool_descriptions: HashMap::new(),
+            max_steps: REACT_DEFAULT_MAX_STEPS,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReactFinishReason {
+    #[default]
+    Answer,
+    MaxSteps,
+}
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ReactAgentOutput {
+    pub answer: String,
+    //调用模型的次数
+    pub steps: usize,
+    pub finish_reason: ReactFinishReason,
+    pub transcript: Vec<ChatMessage>,
+}
+
+#[derive(Default, Debug)]
+pub struct ReactAgent {}
+
+impl ReactAgent {
+    pub async fn tool_definitions(
+        ctx: &Ctx,
+        cfg: &ReactAgentCfg,
+    ) -> anyhow::Result<Vec<ToolDefinition>> {
+        let mut list = vec![];
+        for name in cfg.tools.iter() {
+            let service = match ctx.rt.load_service(name.as_str()).await {
+                Some(s) => s,
+                None => return anyhow::anyhow!("react_agent tool[{}] not found", name).err(),
+            };
+            let parameters = service
+                .input_schema()
+                .unwrap_or_else(|| json!({"type":"object"}));
+            let description = cfg.tool_descriptions.get(name).cloned().unwrap_or_default();
+            list.push(ToolDefinition::function(name, description, parameters));
+        }
+        Ok(list)
+    }
+    //工具的错误作为结果返回给模型，由模型决定如何继续；超出预算时直接返回错误
+    pub async fn call_tool(
+        ctx: &Ctx,
+        cfg: &ReactAgentCfg,
+        node: &str,
+        step: usize,
+        call: &ToolCall,
+    ) -> anyhow::Result<String> {
+        let name = call.function.name.as_str();
+        if !cfg.tools.iter().any(|x| x == name) {
+            return Ok(format!("error: tool[{}] is not available", name));
+        }
+        let args = if call.function.arguments.trim().is_empty() {
+            json!({})
+        } else {
+            match serde_json::from_str::<Value>(call.function.arguments.as_str()) {
+                Ok(v) => v,
+                Err(e) => {
+                    return Ok(format!(
+                        "error: invalid arguments[{}]: {}",
+                        call.function.arguments, e
+                    ))
+                }
+            }
+        };
+        let se = ServiceEntity::new(JsonInput::default().add_transform_value("*", args))
+            .set_node_name(format!("{}.{}[{}]", node, name, step))
+            .set_service_name(name);
+        match ctx
+            .sub_call(se, false)
+            .await
+            .and_then(|o| o.into::<Value>())
+        {
+            Ok(Value::String(s)) => Ok(s),
+            Ok(v) => Ok(v.to_string()),
+            Err(e) => match e.downcast_ref::<Error>() {
+                Some(Error::BudgetExceeded { .. }) => Err(e),
+                _ => Ok(format!("error: {}", e)),
+            },
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl JsonServiceExt<ReactAgentCfg, ReactAgentOutput> for ReactAgent {
+    async fn call(
+        &self,
+        ctx: Ctx,
+        mut cfg: ReactAgentCfg,
+        se: ServiceEntity,
+    ) -> anyhow::Result<ReactAgentOutput> {
+        if cfg.max_steps == 0 {
+            cfg.max_steps = REACT_DEFAULT_MAX_STEPS;
+        }
+        let tools = Self::tool_definitions(&ctx, &cfg).await?;
+        let mut out = ReactAgentOutput {
+            finish_reason: ReactFinishReason::MaxSteps,
+            ..Default::default()
+        };
+        if !cfg.system.is_empty() {
+            out.transcript.push(ChatMessage::system(cfg.system.clone()));
+        }
+        out.transcript.append(&mut cfg.messages);
+        if !cfg.prompt.is_empty() {
+            out.transcript.push(ChatMessage::user(cfg.prompt.clone()));
+        }
+
+        while out.steps < cfg.max_steps {
+            let step = out.steps;
+            out.steps += 1;
+            //消息内容可能包含${{，只能以值的方式传入
+            let input = JsonInput::default()
+                .set_default_json(cfg.model_args.clone().into())
+                .add_transform_value("messages", serde_json::to_value(&out.transcript)?)
+                .add_transform_value("tools", serde_json::to_value(&tools)?);
+            let model = ServiceEntity::new(input)
+                .set_node_name(format!("{}.{}[{}]", se.node_name, cfg.model, step))
+                .set_service_name(cfg.model.clone());
+            let resp = ctx.sub_call(model, false).await?.into::<Value>()?;
+            let message = match resp.get("message") {
+                Some(m) => serde_json::from_value::<ChatMessage>(m.clone())?,
+                None => ChatMessage::assistant(resp["content"].as_str().unwrap_or_default()),
+            };
+            let calls = message.tool_calls.clone();
+            out.transcript.push(message);
+            if calls.is_empty() {
+                out.answer = out
+                    .transcript
+                    .last()
+                    .map(|x| x.content.clone())
+                    .unwrap_or_default();
+                out.finish_reason = ReactFinishReason::Answer;
+                break;
+            }
+            for call in calls.iter() {
+                let result = Self::call_tool(&ctx, &cfg, se.node_name.as_str(), step, call).await?;
+                let mut msg = ChatMessage::tool(call.id.clone(), result);
+                msg.name = Some(call.function.name.clone());
+                out.transcript.push(msg);
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{Budget, Ctx, CtxSerdeExt, EngineRT, Error, JsonInput, ServiceEntity, Usage};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::{Obj, ServiceLoaderWrap};
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default, serde::Serialize, serde::Deserialize)]
+    #[serde(default)]
+    struct AddReq {
+        a: i64,
+        b: i64,
+    }
+
+    #[tokio::test]
+    async fn test_react_agent() {
+        let seen_tools = Arc::new(Mutex::new(Value::Null));
+        let st = seen_tools.clone();
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    //没有工具结果时调用add，否则给出答案
+                    .register_json_ext_service("mock_model", move |_ctx, io: Obj, _se| {
+                        let st = st.clone();
+                        async move {
+                            let io = Value::from(io);
+                            *st.lock().unwrap() = io["tools"].clone();
+                            let tool = io["messages"]
+                                .as_array()
+                                .and_then(|x| x.iter().find(|m| m["role"] == "tool"))
+                                .cloned();
+                            if let Some(last) = tool {
+                                return Ok(json!({"message":{"role":"assistant","content":format!("the answer is {}",last["content"].as_str().unwrap_or(""))}}));
+                            }
+                            Ok(json!({"message":{"role":"assistant","content":null,"tool_calls":[
+                                {"id":"c1","type":"function","function":{"name":"add","arguments":"{\"a\":1,\"b\":2}"}},
+                                {"id":"c2","type":"function","function":{"name":"rm_rf","arguments":"{}"}}
+                            ]}}))
+                        }
+                    })
+                    .register_json_ext_service_with_schema(
+                        "add",
+                        |_ctx, req: AddReq, _se| async move { Ok(req.a + req.b) },
+                        None,
+                        None,
+                    ),
+            )
+            .build();
+        let plan = |max_steps: usize| {
+            Pipeline::default()
+                .step((
+                    "react_agent",
+                    JsonInput::default().set_default_json(json!({
+                        "model":"mock_model",
+                        "system":"you can add",
+                        "prompt":"${{start.q}}",
+                        "tools":["add"],
+                        "tool_descriptions":{"add":"a + b"},
+                        "max_steps":max_steps,
+                    })),
+                ))
+                .check()
+                .unwrap()
+        };
+        let res: Value = rt
+            .ctx(plan(4))
+            .serde_run(json!({"q":"1+2=?"}))
+            .await
+            .unwrap();
+        assert_eq!(res["answer"], "the answer is 3");
+        assert_eq!(
+            (res["steps"].as_u64(), res["finish_reason"].as_str()),
+            (Some(2), Some("answer"))
+        );
+        let roles = res["transcript"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["role"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            roles,
+            vec!["system", "user", "assistant", "tool", "tool", "assistant"]
+        );
+        assert!(res["transcript"][4]["content"]
+            .as_str()
+            .unwrap()
+            .contains("not available"));
+
+        let tools = seen_tools.lock().unwrap().clone();
+        assert_eq!(tools[0]["function"]["name"], "add");
+        assert_eq!(tools[0]["function"]["description"], "a + b");
+        assert_eq!(
+            tools[0]["function"]["parameters"]["properties"]["b"]["type"],
+            "integer"
+        );
+
+        let res: Value = rt
+            .ctx(plan(1))
+            .serde_run(json!({"q":"1+2=?"}))
+            .await
+            .unwrap();
+        assert_eq!(res["finish_reason"], "max_steps");
+        assert_eq!(res["answer"], "");
+    }
+
+    #[tokio::test]
+    async fn test_react_agent_budget() {
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("mock_model", |_ctx, _io: Obj, _se| async move {
+                        Ok(json!({"message":{"role":"assistant","content":null,"tool_calls":[
+                            {"id":"c1","type":"function","function":{"name":"spend","arguments":"{}"}}
+                        ]}}))
+                    })
+                    //工具自身的用量超出预算
+                    .register_json_ext_service("spend", |ctx: Ctx, _io: Obj, se: ServiceEntity| async move {
+                        ctx.record_usage(se.node_name.as_str(), Usage::new(100, 0))?;
+                        Ok(json!("spent"))
+                    }),
+            )
+            .set_budget(Budget::default().set_max_tokens_per_run(50))
+            .build();
+        let plan = Pipeline::default()
+            .step((
+                "react_agent",
+                JsonInput::default().set_default_json(json!({
+                    "model":"mock_model",
+                    "prompt":"go",
+                    "tools":["spend"],
+                    "max_steps":3,
+                })),
+            ))
+            .check()
+            .unwrap();
+        let err = rt
+            .ctx(plan)
+            .serde_run::<_, Value>(json!({}))
+            .await
+            .unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::BudgetExceeded { node, .. }) => {
+                assert_eq!(node, "react_agent.spend[0]")
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+}
@@ -5,13 +5,33 @@ use serde_json::Value;
 use wd_tools::PFErr;
 use crate::plan::graph::Graph;
 
-#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 pub enum WorkflowPlan {
     #[default]
     None,
     DAG(DAG),
     GRAPH(Graph),
+    //引用EngineRT.register_plan注册的plan
+    REF { name: String },
+}
+
+impl WorkflowPlan {
+    pub fn is_none(&self) -> bool {
+        matches!(self, WorkflowPlan::None)
+    }
+    //在新的ctx中运行，与当前ctx共享engine和env
+    pub fn fork(self, ctx: &Ctx) -> anyhow::Result<Ctx> {
+        match self {
+            WorkflowPlan::None => anyhow::anyhow!("WorkflowPlan is nil").err(),
+            WorkflowPlan::DAG(dag) => Ok(ctx.fork(dag)),
+            WorkflowPlan::GRAPH(g) => Ok(ctx.fork(g)),
+            WorkflowPlan::REF { name } => match ctx.rt.load_plan(name.as_str()) {
+                Some(p) => Ok(ctx.fork(p)),
+                None => anyhow::anyhow!("WorkflowPlan.REF plan[{}] not registered", name).err(),
+            },
+        }
+    }
 }
 
 #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
@@ -31,18 +51,10 @@ impl JsonServiceExt<WorkflowConfig, Value> for Workflow {
         se: ServiceEntity,
     ) -> anyhow::Result<Value> {
         let input = cfg.input;
-        match cfg.plan {
-            WorkflowPlan::None => {
-                return anyhow::anyhow!("[Workflow::{}] plan is nil", se.node_name).err()
-            }
-            WorkflowPlan::DAG(dag) => ctx.fork(dag),
-            WorkflowPlan::GRAPH(g) => {
-                ctx.fork(g)
-                // return anyhow::anyhow!("[Workflow::{}] not support graph", se.node_name).err()
-            }
+        if cfg.plan.is_none() {
+            return anyhow::anyhow!("[Workflow::{}] plan is nil", se.node_name).err();
         }
-        .run::<Value, _>(input.into())
-        .await
+        cfg.plan.fork(&ctx)?.run::<Value, _>(input.into()).await
     }
 }
 
@@ -1,6 +1,10 @@
 use crate::core::{JsonServiceExt, MapServiceLoader, Service, ServiceLoader};
-use crate::service::agent::Workflow;
-use crate::service::flow::{End, Select, Start,Batch};
+use crate::service::agent::{ReactAgent, Workflow};
+use crate::service::llm::ChatCompletion;
+use crate::service::memory::{MemoryLoad, MemorySave};
+use crate::service::prompt::PromptRender;
+use crate::service::flow::{End, Select, Start,Batch, Reduce, Loop};
+use serde_json::Value;
 use std::sync::Arc;
 
 pub struct ServiceLoaderWrap {
@@ -46,6 +50,27 @@ impl ServiceLoaderWrap {
         self.map_loader = self.map_loader.register_json_ext_service(name, service);
         self
     }
+    #[allow(unused)]
+    pub fn register_json_ext_service_with_schema<N: Into<String>, T, In, Out>(
+        mut self,
+        name: N,
+        service: T,
+        input_schema: Option<Value>,
+        output_schema: Option<Value>,
+    ) -> Self
+    where
+        T: JsonServiceExt<In, Out> + Sync + 'static,
+        In: serde::Serialize + serde::de::DeserializeOwned + Send + Sync + Default + 'static,
+        Out: serde::Serialize + Send + Sync + Default + 'static,
+    {
+        self.map_loader = self.map_loader.register_json_ext_service_with_schema(
+            name,
+            service,
+            input_schema,
+            output_schema,
+        );
+        self
+    }
 }
 impl Default for ServiceLoaderWrap {
     fn default() -> Self {
@@ -53,8 +78,15 @@ impl Default for ServiceLoaderWrap {
             .register_json_ext_service("start", Start {})
             .register_json_ext_service("end", End {})
             .register_json_ext_service("batch", Batch::default())
+            .register_json_ext_service("flow_reduce", Reduce::default())
+            .register_json_ext_service("flow_loop", Loop::default())
             .register_json_ext_service("workflow", Workflow::new())
-            .register_json_ext_service("flow_select", Select::default());
+            .register_json_ext_service("react_agent", ReactAgent::default())
+            .register_json_ext_service("flow_select", Select::default())
+            .register_json_ext_service("chat_completion", ChatCompletion::default())
+            .register_json_ext_service("memory_load", MemoryLoad::default())
+            .register_json_ext_service("memory_save", MemorySave::default())
+            .register_json_ext_service("prompt_render", PromptRender::default());
         // .register_service("var", Var::<DefaultVarMap>::default());
         Self::new().set_map_loader(loader)
     }
@@ -69,4 +101,11 @@ impl ServiceLoader for ServiceLoaderWrap {
         }
         self.service_loader.load(name).await
     }
+    fn names(&self) -> Vec<String> {
+        let mut list = self.map_loader.names();
+        list.extend(self.service_loader.names());
+        list.sort();
+        list.dedup();
+        list
+    }
 }
@@ -1,24 +1,67 @@
 use std::ops::DerefMut;
 use std::sync::Arc;
-use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde_json::{Map, Value};
 use tokio::sync::Mutex;
 use wd_tools::{PFArc, PFErr};
 use wd_tools::pool::ParallelPool;
-use crate::core::{Ctx, JsonInput, JsonServiceExt, ServiceEntity};
+use crate::core::{Ctx, EnvExt, JsonInput, JsonServiceExt, ServiceEntity};
+use crate::service::agent::WorkflowPlan;
 use crate::service::ext::Obj;
 
 #[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
 pub struct BatchCfg {
-    pub inputs:Vec<Value>,
+    //数组：逐项执行；对象：逐个键值对执行，传入key和value，输出也是对象
+    pub inputs:Value,
+    //inputs为多个等长数组(数组或对象形式)，按下标组合成一项
+    pub zip: bool,
+    //每chunk_size项合并为一次调用，input为数组，输出需为等长数组
+    pub chunk_size: usize,
     pub batch_max: usize,
     pub service: String,
+    //与service二选一，每一项在fork出的ctx中运行一次plan，end节点的输出作为该项的结果
+    pub plan: WorkflowPlan,
     pub format : Obj,
+    pub on_error: BatchOnError,
+    //每一项的输出以 节点名#index 写入vars，可以直接引用，如 ${{batch_add#2}} 或 quote ["batch_add#2"]
+    pub keep_item_vars: bool,
+}
+//每完成一项通过env发出
+#[derive(Default, Debug, Clone)]
+pub struct BatchProgressEvent{
+    pub node:String,
+    pub completed:usize,
+    pub total:usize,
+}
+//fail_fast:任意一项失败则整个节点失败
+//collect:失败项的输出为null，错误记录在errors中
+//skip:丢弃失败项，只保留成功的输出
+#[derive(Default, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOnError {
+    #[default]
+    FailFast,
+    Collect,
+    Skip,
+}
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct BatchItemError{
+    pub index:usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key:Option<String>,
+    pub message:String,
 }
 #[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
 pub struct BatchResult{
-    pub outputs:Vec<Value>
+    //与inputs形状一致：数组或对象
+    pub outputs:Value,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors:Vec<BatchItemError>,
+    pub success:usize,
+    pub failure:usize,
 }
 
 
@@ -32,13 +75,19 @@ impl Batch {
             .add_transform_value("input",input)
             .add_transform_value("index",index)
     }
-    pub async fn set_error(me:&Arc<Mutex<Option<anyhow::Error>>>,i:usize,err:anyhow::Error){
+    pub async fn set_error(me:&Arc<Mutex<Vec<BatchItemError>>>,i:usize,err:anyhow::Error){
         let mut lock = me.lock().await;
-        lock.replace(anyhow::anyhow!("Batch[{i}] error={}",err));
+        lock.push(BatchItemError{ index: i, key: None, message: err.to_string() });
+    }
+    pub async fn get_error(me:&Arc<Mutex<Vec<BatchItemError>>>)->Option<anyhow::Error>{
+        let lock = me.lock().await;
+        lock.iter().min_by_key(|x|x.index).map(|x|anyhow::anyhow!("Batch[{}] error={}",x.index,x.message))
     }
-    pub async fn get_error(me:&Arc<Mutex<Option<anyhow::Error>>>)->Option<anyhow::Error>{
+    pub async fn get_errors(me:&Arc<Mutex<Vec<BatchItemError>>>)->Vec<BatchItemError>{
         let mut lock = me.lock().await;
-        lock.take()
+        let mut list = std::mem::take(lock.deref_mut());
+        list.sort_by_key(|x|x.index);
+        list
     }
     pub async fn set_output(ma:&Arc<Mutex<Vec<Value>>>,i:usize,out:Value){
         let mut lock = ma.lock().await;
@@ -48,70 +97,189 @@ impl Batch {
         let mut lock = ma.lock().await;
         std::mem::replace(lock.deref_mut(),Vec::new())
     }
-    // pub async fn call_service(){
-    //
-    // }
+    //将inputs展开为逐项的值，对象形式同时返回key
+    pub fn expand_inputs(inputs:Value,zip:bool)->anyhow::Result<(Vec<Value>,Option<Vec<String>>)>{
+        if zip {
+            let (names,lists) = match inputs {
+                Value::Array(list) => (None,list),
+                Value::Object(map) => {
+                    let (names,lists):(Vec<_>,Vec<_>) = map.into_iter().unzip();
+                    (Some(names),lists)
+                }
+                Value::Null => return Ok((vec![],None)),
+                _ => return anyhow::anyhow!("BatchCfg.zip: inputs must be array or object of arrays").err(),
+            };
+            let mut lists = lists.into_iter().map(|x|match x {
+                Value::Array(list) => Ok(list.into_iter()),
+                _ => anyhow::anyhow!("BatchCfg.zip: every input must be an array").err(),
+            }).collect::<anyhow::Result<Vec<_>>>()?;
+            let len = lists.first().map(|x|x.len()).unwrap_or(0);
+            if let Some(x) = lists.iter().find(|x|x.len() != len) {
+                return anyhow::anyhow!("BatchCfg.zip: arrays length mismatch, {} != {}",x.len(),len).err()
+            }
+            let mut items = vec![];
+            for _ in 0..len {
+                let row = lists.iter_mut().map(|x|x.next().unwrap_or_default());
+                items.push(match names {
+                    Some(ref names) => Value::Object(names.iter().cloned().zip(row).collect()),
+                    None => Value::Array(row.collect()),
+                });
+            }
+            return Ok((items,None))
+        }
+        match inputs {
+            Value::Array(list) => Ok((list,None)),
+            Value::Object(map) => {
+                let (keys,items) = map.into_iter().unzip();
+                Ok((items,Some(keys)))
+            }
+            Value::Null => Ok((vec![],None)),
+            _ => anyhow::anyhow!("BatchCfg.inputs must be array or object").err(),
+        }
+    }
+    //item格式化后的值作为plan的start输入
+    pub async fn run_plan(ctx:Ctx,plan:WorkflowPlan,input:JsonInput)->anyhow::Result<Value>{
+        let mut val = Value::Object(Map::new());
+        input.transform(ctx.clone(),&mut val,None).await?;
+        plan.fork(&ctx)?.run::<Value,_>(val).await
+    }
 }
 
 #[async_trait::async_trait]
 impl JsonServiceExt<BatchCfg, BatchResult> for Batch {
     async fn call(&self, ctx: Ctx, mut cfg: BatchCfg, se: ServiceEntity) -> anyhow::Result<BatchResult> {
-        let s = if let Some(s) = ctx.rt.load_service(cfg.service.as_str()).await {
-            s
-        }else{
+        let use_plan = !cfg.plan.is_none();
+        if use_plan {
+            if !cfg.service.is_empty() {
+                return anyhow::anyhow!("BatchCfg.call:service[{}] and plan can not both be set", cfg.service).err()
+            }
+        }else if ctx.rt.load_service(cfg.service.as_str()).await.is_none() {
             return anyhow::anyhow!("BatchCfg.call:service[{}] not found", cfg.service).err()
-        };
+        }
         if cfg.batch_max<=0{
             wd_log::log_field("Batch.cfg.batch_max",0).warn("update max = 1");
             cfg.batch_max = 1
         }
 
+        let (items,keys) = Self::expand_inputs(std::mem::take(&mut cfg.inputs),cfg.zip)?;
+        //每个单元对应一次调用，包含若干项的下标
+        let units = if cfg.chunk_size > 0 {
+            (0..items.len()).collect::<Vec<_>>().chunks(cfg.chunk_size).map(|x|x.to_vec()).collect::<Vec<_>>()
+        }else{
+            (0..items.len()).map(|i|vec![i]).collect()
+        };
+        let chunked = cfg.chunk_size > 0;
+
         let pp = ParallelPool::new(cfg.batch_max);
-        let result_list = vec![Value::Null;cfg.inputs.len()];
-        let output = Mutex::new(result_list).arc();
-        let err = Mutex::new(None).arc();
-
-        for (i,e) in cfg.inputs.into_iter().enumerate() {
-            let input = Self::make_sub_input_from_format(cfg.format.clone(), i, e);
-            let se = ServiceEntity::new(input)
-                // .set_service(s.clone())
-                .set_node_name(format!("{}_{}",se.node_name,i))
+        let total = items.len();
+        let output = Mutex::new(vec![Value::Null;total]).arc();
+        let err = Mutex::new(Vec::new()).arc();
+        let on_error = cfg.on_error;
+        let keep_var = cfg.keep_item_vars;
+        let completed = Arc::new(AtomicUsize::new(0));
+
+        for (i,unit) in units.into_iter().enumerate() {
+            let mut input = if chunked {
+                let list = unit.iter().map(|x|items[*x].clone()).collect::<Vec<_>>();
+                Self::make_sub_input_from_format(cfg.format.clone(), i, Value::Array(list))
+            }else{
+                Self::make_sub_input_from_format(cfg.format.clone(), i, items[i].clone())
+            };
+            if let Some(ref keys) = keys {
+                input = if chunked {
+                    input.add_transform_value("key",unit.iter().map(|x|keys[*x].clone()).collect::<Vec<_>>())
+                }else{
+                    input.add_transform_value("key",keys[i].clone())
+                        .add_transform_value("value",items[i].clone())
+                };
+            }
+            let mut item = ServiceEntity::new(input)
+                .set_node_name(format!("{}#{}", se.node_name, i))
                 .set_service_name(cfg.service.clone());
-            let s = s.clone();
+            let plan = cfg.plan.clone();
             let ctx = ctx.clone();
             let aerr = err.clone();
             let output = output.clone();
+            let completed = completed.clone();
+            let node = se.node_name.clone();
             pp.launch(async move {
-                let result = s.call(ctx.clone(),se).await;
-                let out = match result {
-                    Ok(o) => o,
-                    Err(e) => {
-                        return Self::set_error(&aerr,i,e).await;
+                let result = if use_plan {
+                    let input = item.transform_config(|c:Option<JsonInput>|c).unwrap_or_default();
+                    Self::run_plan(ctx.clone(),plan,input).await
+                }else{
+                    //经过中间件执行，与普通节点一致
+                    match ctx.sub_call(item,keep_var).await {
+                        Ok(o) => o.into::<Value>(),
+                        Err(e) => Err(e),
                     }
                 };
-                let res = out.into::<Value>();
-                match res {
-                    Ok(v) => Self::set_output(&output,i,v).await,
-                    Err(e) => Self::set_error(&aerr,i,e).await,
+                //合并调用的输出按下标拆回每一项
+                let result = match result {
+                    Ok(Value::Array(list)) if chunked && list.len() == unit.len() => Ok(list),
+                    Ok(v) if chunked => anyhow::anyhow!("chunk output must be an array of {} items, got {}",unit.len(),v).err(),
+                    Ok(v) => Ok(vec![v]),
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(list) => for (x,v) in unit.iter().zip(list) {
+                        Self::set_output(&output,*x,v).await
+                    },
+                    Err(e) => for x in unit.iter() {
+                        Self::set_error(&aerr,*x,anyhow::anyhow!("{}",e)).await
+                    },
                 };
+                let event = BatchProgressEvent{
+                    node,
+                    completed: completed.fetch_add(unit.len(),Ordering::SeqCst)+unit.len(),
+                    total,
+                };
+                if let Err(e) = ctx.get_env().feedback_ext(event).await {
+                    wd_log::log_field("error",e).warn("batch feedback progress failed");
+                }
             }).await;
+            if on_error == BatchOnError::FailFast {
+                if let Some(e) = Self::get_error(&err).await {
+                    return Err(e);
+                }
+            }
+        }
+        pp.wait_over().await;
+        if on_error == BatchOnError::FailFast {
             if let Some(e) = Self::get_error(&err).await {
                 return Err(e);
             }
         }
-        pp.wait_over().await;
         let output_list = Self::get_output(&output).await;
+        let mut errors = Self::get_errors(&err).await;
+        let failure = errors.len();
+        let success = output_list.len() - failure;
+        let mut keep = vec![true;output_list.len()];
+        if on_error == BatchOnError::Skip {
+            errors.drain(..).for_each(|x|keep[x.index] = false);
+        }
+        if let Some(ref keys) = keys {
+            errors.iter_mut().for_each(|x|x.key = Some(keys[x.index].clone()));
+        }
+        let kept = output_list.into_iter().zip(keep).enumerate().filter(|(_,(_,k))|*k).map(|(i,(v,_))|(i,v));
+        let outputs = match keys {
+            Some(keys) => Value::Object(kept.map(|(i,v)|(keys[i].clone(),v)).collect()),
+            None => Value::Array(kept.map(|(_,v)|v).collect()),
+        };
 
-        Ok(BatchResult{ outputs: output_list })
+        Ok(BatchResult{ outputs, errors, success, failure })
     }
 }
 
 #[cfg(test)]
 mod test{
     use serde_json::{json, Number, Value};
-    use crate::core::{CtxSerdeExt, EngineRT, JsonInput, ServiceEntity};
+    use wd_tools::PFErr;
+    use std::sync::{Arc, Mutex};
+    use crate::core::{CabinetEnv, Ctx, CtxSerdeExt, EngineRT, EnvExt, JsonInput, ServiceEntity};
+    use crate::service::flow::BatchProgressEvent;
     use crate::plan::graph::{Graph, GraphNode};
-    use crate::service::ext::{ServiceLoaderWrap};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::{Obj, ServiceLoaderWrap};
 
     #[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
     #[serde(default)]
@@ -186,4 +354,179 @@ mod test{
         assert_eq!(res.outputs[9],10);
     }
 
+    #[tokio::test]
+    async fn test_batch_on_error(){
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service(
+                        "check",
+                        |_ctx, io:AddInOut, _se| async move {
+                            if io.input.is_multiple_of(3) {
+                                return anyhow::anyhow!("malformed document {}",io.input).err();
+                            }
+                            Ok(io.input)
+                        })
+            )
+            .build();
+        let plan = |on_error:&str| {
+            let batch_cfg = json!({
+                "batch_max": 2,
+                "inputs":[1,2,3,4,5,6],
+                "service":"check",
+                "on_error":on_error,
+            });
+            Graph::default()
+                .node(("start",r#"{"service_name":"start","config":{}}"#))
+                .node(GraphNode::new("batch").set_service_entity_json("batch",JsonInput::default().set_default_json(batch_cfg)))
+                .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"*":{"quote":"batch"}}}}"#))
+                .edges([("start","batch"),("batch","end")])
+                .check()
+                .unwrap()
+        };
+
+        let res:Value = rt.ctx(plan("collect")).serde_run(json!({})).await.unwrap();
+        assert_eq!(res["outputs"], json!([1,2,null,4,5,null]));
+        assert_eq!(res["errors"][1]["index"], 5);
+        assert!(res["errors"][0]["message"].as_str().unwrap().contains("malformed document 3"));
+        assert_eq!((res["success"].as_u64(), res["failure"].as_u64()), (Some(4), Some(2)));
+
+        let res:Value = rt.ctx(plan("skip")).serde_run(json!({})).await.unwrap();
+        assert_eq!(res["outputs"], json!([1,2,4,5]));
+        assert!(res.get("errors").is_none());
+
+        let err = rt.ctx(plan("fail_fast")).serde_run::<_,Value>(json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("Batch[2]"),"{}",err);
+    }
+
+    #[tokio::test]
+    async fn test_batch_plan(){
+        //每一项执行 clean -> summarize
+        let chunk = Pipeline::default()
+            .step(("clean", JsonInput::default()))
+            .step(("summarize", JsonInput::default()))
+            .check()
+            .unwrap();
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("clean", |_ctx, io:Obj, _se| async move {
+                        let io = Value::from(io);
+                        Ok(json!({"index":io["index"],"text":io["input"].as_str().unwrap_or("").trim()}))
+                    })
+                    .register_json_ext_service("summarize", |_ctx, io:Obj, _se| async move {
+                        let io = Value::from(io);
+                        Ok(format!("{}:{}",io["index"],io["text"].as_str().unwrap_or("").to_uppercase()))
+                    })
+            )
+            .register_plan("chunk", chunk)
+            .build();
+
+        let batch_cfg = json!({
+            "batch_max": 2,
+            "inputs":"${{start.docs}}",
+            "plan":{"type":"REF","name":"chunk"},
+        });
+        let plan = Graph::default()
+            .node(("start",r#"{"service_name":"start","config":{}}"#))
+            .node(GraphNode::new("batch").set_service_entity_json("batch",JsonInput::default().set_default_json(batch_cfg)))
+            .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"list":{"quote":"batch.outputs"}}}}"#))
+            .edges([("start","batch"),("batch","end")])
+            .check()
+            .unwrap();
+        let res:Value = rt.ctx(plan).serde_run(json!({"docs":[" a ","b"," c"]})).await.unwrap();
+        assert_eq!(res["list"], json!(["0:A","1:B","2:C"]));
+    }
+
+    #[tokio::test]
+    async fn test_batch_middleware(){
+        let names = Arc::new(Mutex::new(vec![]));
+        let mn = names.clone();
+        let env = Arc::new(CabinetEnv::new());
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("double", |_ctx, io:AddInOut, _se| async move {
+                        Ok(io.input * 2)
+                    })
+            )
+            .append_service_middle(move |ctx: Ctx, se:ServiceEntity| {
+                mn.lock().unwrap().push(se.node_name.clone());
+                ctx.next(se)
+            })
+            .build();
+        let batch_cfg = json!({
+            "inputs":[1,2,3],
+            "service":"double",
+            "keep_item_vars":true,
+        });
+        let plan = Graph::default()
+            .node(("start",r#"{"service_name":"start","config":{}}"#))
+            .node(GraphNode::new("batch_add").set_service_entity_json("batch",JsonInput::default().set_default_json(batch_cfg)))
+            .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"list":{"quote":"batch_add.outputs"},"third":{"quote":"[\"batch_add#2\"]"}},"default_json":{"next":"${{ batch_add#2 + 1 }}"}}}"#))
+            .edges([("start","batch_add"),("batch_add","end")])
+            .check()
+            .unwrap();
+        let res:Value = rt.ctx(plan).set_env(env.clone()).serde_run(json!({})).await.unwrap();
+        assert_eq!(res["list"], json!([2,4,6]));
+        assert_eq!(res["third"], json!(6));
+        assert_eq!(res["next"], json!(7));
+
+        let mut names = names.lock().unwrap().clone();
+        names.sort();
+        assert_eq!(names, vec!["batch_add","batch_add#0","batch_add#1","batch_add#2","end","start"]);
+        let event = env.watch_ext::<BatchProgressEvent>().await.unwrap().unwrap();
+        assert_eq!((event.node.as_str(), event.completed, event.total), ("batch_add", 3, 3));
+    }
+
+    #[tokio::test]
+    async fn test_batch_shapes(){
+        let chunk_sizes = Arc::new(Mutex::new(vec![]));
+        let cs = chunk_sizes.clone();
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("pair", |_ctx, io:Obj, _se| async move {
+                        let io = Value::from(io);
+                        match io["input"].as_array() {
+                            Some(list) => Ok(json!(list.iter().filter_map(|x|x.as_i64()).sum::<i64>())),
+                            None => Ok(json!(format!("{}={}",io["key"].as_str().unwrap_or(""),io["value"]))),
+                        }
+                    })
+                    .register_json_ext_service("upper_many", move |_ctx, io:Obj, _se| {
+                        let cs = cs.clone();
+                        async move {
+                            let list = Value::from(io)["input"].as_array().cloned().unwrap_or_default();
+                            cs.lock().unwrap().push(list.len());
+                            Ok(list.iter().map(|x|x.as_str().unwrap_or("").to_uppercase()).collect::<Vec<_>>())
+                        }
+                    })
+            )
+            .build();
+        let plan = |cfg:Value| Graph::default()
+            .node(("start",r#"{"service_name":"start","config":{}}"#))
+            .node(GraphNode::new("batch").set_service_entity_json("batch",JsonInput::default().set_default_json(cfg)))
+            .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"*":{"quote":"batch"}}}}"#))
+            .edges([("start","batch"),("batch","end")])
+            .check()
+            .unwrap();
+
+        //对象进，对象出
+        let res:Value = rt.ctx(plan(json!({"inputs":{"a":1,"b":2},"service":"pair"}))).serde_run(json!({})).await.unwrap();
+        assert_eq!(res["outputs"], json!({"a":"a=1","b":"b=2"}));
+
+        //多个等长数组按下标组合
+        let res:Value = rt.ctx(plan(json!({"inputs":[[1,2,3],[10,20,30]],"zip":true,"service":"pair"}))).serde_run(json!({})).await.unwrap();
+        assert_eq!(res["outputs"], json!([11,22,33]));
+        let err = rt.ctx(plan(json!({"inputs":[[1,2],[10]],"zip":true,"service":"pair"}))).serde_run::<_,Value>(json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("length mismatch"),"{}",err);
+
+        //每两项合并为一次调用
+        let res:Value = rt.ctx(plan(json!({"inputs":["a","b","c","d","e"],"chunk_size":2,"service":"upper_many"}))).serde_run(json!({})).await.unwrap();
+        assert_eq!(res["outputs"], json!(["A","B","C","D","E"]));
+        let mut sizes = chunk_sizes.lock().unwrap().clone();
+        sizes.sort();
+        assert_eq!(sizes, vec![1,2,2]);
+    }
+
 }
\ No newline at end of file
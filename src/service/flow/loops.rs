@@ -0,0 +1,153 @@
+use serde_json::{json, Value};
+use wd_tools::PFErr;
+use crate::core::{Ctx, EnvExt, JsonServiceExt, ServiceEntity};
+use crate::service::agent::WorkflowPlan;
+use crate::service::ext::Obj;
+use crate::service::flow::SelectNode;
+use crate::utils::path;
+
+pub const LOOP_DEFAULT_MAX_ITERATIONS: usize = 10;
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LoopCfg {
+    pub plan: WorkflowPlan,
+    //每一轮plan的start输入为{input,iteration,last}，last为上一轮的输出
+    pub input: Obj,
+    //每一轮之后判断，为true则结束；不在节点入口渲染，其中的${{output.xxx}}按{output,iteration}解析
+    pub until: SelectNode,
+    pub max_iterations: usize,
+    pub delay_ms: u64,
+}
+#[derive(Default, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopStopReason {
+    #[default]
+    Condition,
+    MaxIterations,
+}
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LoopResult {
+    //最后一轮的输出
+    pub output: Value,
+    pub outputs: Vec<Value>,
+    pub iterations: usize,
+    pub reason: LoopStopReason,
+}
+//每一轮结束后通过env发出
+#[derive(Default, Debug, Clone)]
+pub struct LoopIterationEvent {
+    pub node: String,
+    pub iteration: usize,
+    pub output: Value,
+}
+
+#[derive(Default, Debug)]
+pub struct Loop {}
+
+#[async_trait::async_trait]
+impl JsonServiceExt<LoopCfg, LoopResult> for Loop {
+    fn raw_fields(&self) -> Vec<&'static str> {
+        vec!["until"]
+    }
+    async fn call(&self, ctx: Ctx, mut cfg: LoopCfg, se: ServiceEntity) -> anyhow::Result<LoopResult> {
+        if cfg.plan.is_none() {
+            return anyhow::anyhow!("LoopCfg.call:plan is nil").err()
+        }
+        if cfg.max_iterations == 0 {
+            wd_log::log_field("Loop.cfg.max_iterations", 0).warn(format!("update max = {}", LOOP_DEFAULT_MAX_ITERATIONS));
+            cfg.max_iterations = LOOP_DEFAULT_MAX_ITERATIONS;
+        }
+        let mut result = LoopResult { reason: LoopStopReason::MaxIterations, ..Default::default() };
+        for i in 0..cfg.max_iterations {
+            if i > 0 && cfg.delay_ms > 0 {
+                tokio::time::sleep(tokio::time::Duration::from_millis(cfg.delay_ms)).await;
+            }
+            let input = json!({"input": Value::from(cfg.input.clone()), "iteration": i, "last": result.output});
+            let output = match cfg.plan.clone().fork(&ctx)?.run::<Value, Value>(input).await {
+                Ok(o) => o,
+                Err(e) => return anyhow::anyhow!("Loop[{}] error={}", i, e).err(),
+            };
+            result.iterations = i + 1;
+            result.output = output.clone();
+            result.outputs.push(output.clone());
+
+            let event = LoopIterationEvent { node: se.node_name.clone(), iteration: i, output };
+            if let Err(e) = ctx.get_env().feedback_ext(event).await {
+                wd_log::log_field("error", e).warn("flow_loop feedback iteration failed");
+            }
+            if matches!(cfg.until, SelectNode::None) {
+                continue;
+            }
+            let scope = json!({"output": result.output, "iteration": i});
+            let lookup = |p: &str| path::query(&scope, p).ok();
+            if cfg.until.resolve(&lookup)?.generate_result()? {
+                result.reason = LoopStopReason::Condition;
+                break;
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+    use crate::core::{CtxSerdeExt, EngineRT, JsonInput};
+    use crate::plan::graph::{Graph, GraphNode};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::{Obj, ServiceLoaderWrap};
+
+    #[tokio::test]
+    async fn test_loop() {
+        //第三次生成的结果才能通过检查
+        let generate = Pipeline::default()
+            .step(("generate", JsonInput::default()))
+            .step(("critic", JsonInput::default()))
+            .check()
+            .unwrap();
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("generate", |_ctx, io: Obj, _se| async move {
+                        let io = Value::from(io);
+                        let n = io["iteration"].as_u64().unwrap_or(0);
+                        Ok(json!({"text": format!("{}#{}", io["input"]["topic"].as_str().unwrap_or(""), n), "n": n}))
+                    })
+                    .register_json_ext_service("critic", |_ctx, io: Obj, _se| async move {
+                        let mut io = Value::from(io);
+                        io["approved"] = json!(io["n"].as_u64() >= Some(2));
+                        Ok(io)
+                    })
+            )
+            .register_plan("generate", generate)
+            .build();
+        let plan = |max: usize| {
+            let cfg = json!({
+                "plan": {"type": "REF", "name": "generate"},
+                "input": {"topic": "${{start.topic}}"},
+                "until": {"equal": ["${{output.approved}}", true]},
+                "max_iterations": max,
+                "delay_ms": 1,
+            });
+            Graph::default()
+                .node(("start", r#"{"service_name":"start","config":{}}"#))
+                .node(GraphNode::new("loop").set_service_entity_json("flow_loop", JsonInput::default().set_default_json(cfg)))
+                .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"*":{"quote":"loop"}}}}"#))
+                .edges([("start", "loop"), ("loop", "end")])
+                .check()
+                .unwrap()
+        };
+
+        let res: Value = rt.ctx(plan(5)).serde_run(json!({"topic": "rust"})).await.unwrap();
+        assert_eq!(res["iterations"], 3);
+        assert_eq!(res["reason"], "condition");
+        assert_eq!(res["output"]["text"], "rust#2");
+        assert_eq!(res["outputs"].as_array().map(|x| x.len()), Some(3));
+
+        let res: Value = rt.ctx(plan(2)).serde_run(json!({"topic": "rust"})).await.unwrap();
+        assert_eq!(res["reason"], "max_iterations");
+        assert_eq!(res["outputs"][1]["approved"], false);
+    }
+}
@@ -2,8 +2,12 @@ mod end;
 mod select;
 mod start;
 mod batch;
+mod reduce;
+mod loops;
 
 pub use end::*;
 pub use select::*;
 pub use start::*;
 pub use batch::*;
+pub use reduce::*;
+pub use loops::*;
@@ -0,0 +1,141 @@
+use serde_json::{json, Value};
+use wd_tools::PFErr;
+use crate::core::{Ctx, JsonInput, JsonServiceExt, ServiceEntity};
+use crate::service::agent::WorkflowPlan;
+use crate::service::ext::Obj;
+use crate::service::flow::{Batch, SelectNode};
+use crate::utils::path;
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ReduceCfg {
+    pub inputs: Vec<Value>,
+    //acc的初始值
+    pub init: Value,
+    pub service: String,
+    //与service二选一
+    pub plan: WorkflowPlan,
+    pub format: Obj,
+    //每一步之后判断，为true则提前结束；不在节点入口渲染，其中的${{acc.xxx}}按每一步的{item,index,acc}解析
+    pub until: SelectNode,
+    //每一步的输出以 节点名#index 写入vars，可以直接引用，如 ${{reduce#1}}
+    pub keep_item_vars: bool,
+}
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ReduceResult {
+    pub acc: Value,
+    pub steps: usize,
+    //是否因until提前结束
+    pub stopped: bool,
+}
+
+#[derive(Default, Debug)]
+pub struct Reduce {}
+
+impl Reduce {
+    pub fn make_step_input(format: Obj, index: usize, item: Value, acc: Value) -> JsonInput {
+        JsonInput::default()
+            .set_default_json(format.into())
+            .add_transform_value("item", item)
+            .add_transform_value("index", index)
+            .add_transform_value("acc", acc)
+    }
+}
+
+#[async_trait::async_trait]
+impl JsonServiceExt<ReduceCfg, ReduceResult> for Reduce {
+    fn raw_fields(&self) -> Vec<&'static str> {
+        vec!["until"]
+    }
+    async fn call(&self, ctx: Ctx, cfg: ReduceCfg, se: ServiceEntity) -> anyhow::Result<ReduceResult> {
+        let use_plan = !cfg.plan.is_none();
+        if use_plan {
+            if !cfg.service.is_empty() {
+                return anyhow::anyhow!("ReduceCfg.call:service[{}] and plan can not both be set", cfg.service).err()
+            }
+        } else if ctx.rt.load_service(cfg.service.as_str()).await.is_none() {
+            return anyhow::anyhow!("ReduceCfg.call:service[{}] not found", cfg.service).err()
+        }
+        let mut result = ReduceResult { acc: cfg.init, ..Default::default() };
+        for (i, item) in cfg.inputs.into_iter().enumerate() {
+            let input = Self::make_step_input(cfg.format.clone(), i, item.clone(), result.acc.clone());
+            let acc = if use_plan {
+                Batch::run_plan(ctx.clone(), cfg.plan.clone(), input).await
+            } else {
+                let step = ServiceEntity::new(input)
+                    .set_node_name(format!("{}#{}", se.node_name, i))
+                    .set_service_name(cfg.service.clone());
+                match ctx.sub_call(step, cfg.keep_item_vars).await {
+                    Ok(o) => o.into::<Value>(),
+                    Err(e) => Err(e),
+                }
+            };
+            result.acc = match acc {
+                Ok(o) => o,
+                Err(e) => return anyhow::anyhow!("Reduce[{}] error={}", i, e).err(),
+            };
+            result.steps = i + 1;
+            if matches!(cfg.until, SelectNode::None) {
+                continue;
+            }
+            let scope = json!({"item": item, "index": i, "acc": result.acc});
+            let lookup = |p: &str| path::query(&scope, p).ok();
+            if cfg.until.resolve(&lookup)?.generate_result()? {
+                result.stopped = true;
+                break;
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+    use crate::core::{CtxSerdeExt, EngineRT, JsonInput};
+    use crate::plan::graph::{Graph, GraphNode};
+    use crate::service::ext::{Obj, ServiceLoaderWrap};
+
+    #[tokio::test]
+    async fn test_reduce() {
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default()
+                    .register_json_ext_service("refine", |_ctx, io: Obj, _se| async move {
+                        let io = Value::from(io);
+                        let summary = format!("{}{}", io["acc"]["summary"].as_str().unwrap_or(""), io["item"].as_str().unwrap_or(""));
+                        Ok(json!({"summary": summary, "found": io["item"] == "!"}))
+                    })
+            )
+            .build();
+        let plan = |until: Value| {
+            let cfg = json!({
+                "inputs": "${{start.chunks}}",
+                "init": {"summary": ">"},
+                "service": "refine",
+                "until": until,
+                "keep_item_vars": true,
+            });
+            Graph::default()
+                .node(("start", r#"{"service_name":"start","config":{}}"#))
+                .node(GraphNode::new("reduce").set_service_entity_json("flow_reduce", JsonInput::default().set_default_json(cfg)))
+                .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"*":{"quote":"reduce"},"second":{"quote":"reduce#1"}}}}"#))
+                .edges([("start", "reduce"), ("reduce", "end")])
+                .check()
+                .unwrap()
+        };
+        let input = json!({"chunks": ["a", "b", "!", "c"]});
+
+        let res: Value = rt.ctx(plan(json!("none"))).serde_run(input.clone()).await.unwrap();
+        assert_eq!(res["acc"]["summary"], ">ab!c");
+        assert_eq!(res["second"]["summary"], ">ab");
+        assert_eq!((res["steps"].as_u64(), res["stopped"].as_bool()), (Some(4), Some(false)));
+
+        //找到答案后提前结束
+        let until = json!({"equal": ["${{acc.found}}", true]});
+        let res: Value = rt.ctx(plan(until)).serde_run(input).await.unwrap();
+        assert_eq!(res["acc"]["summary"], ">ab!");
+        assert_eq!((res["steps"].as_u64(), res["stopped"].as_bool()), (Some(3), Some(true)));
+    }
+}
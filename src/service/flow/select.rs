@@ -1,4 +1,6 @@
-use crate::core::{Ctx, JsonServiceExt, Output, ServiceEntity};
+use crate::core::{Ctx, EnvExt, JsonServiceExt, Output, ServiceEntity};
+use crate::utils::string;
+use regex::Regex;
 use serde_json::Value;
 use std::fmt::Display;
 use std::str::FromStr;
@@ -12,8 +14,9 @@ pub struct SelectCfg {
     pub conditions: SelectNode,
     pub true_to_nodes: Vec<String>,
     pub false_to_nodes: Vec<String>,
+    pub explain: bool, // 为true时额外通过env发出SelectExplainEvent
 }
-#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum SelectNode {
     #[default]
@@ -25,8 +28,19 @@ pub enum SelectNode {
     Less(Value, Value),         // <
     LessEqual(Value, Value),    // <=
     Contain(Value, Value),      // 包含
-    Empty(Value),
+    Empty(Value),    // null "" [] {}
     NonEmpty(Value),
+    StartsWith(Value, Value),
+    EndsWith(Value, Value),
+    EqualIgnoreCase(Value, Value),
+    Regex(Value, String),
+    LengthEqual(Value, usize), // 字符串按字符计数，数组和对象按元素计数
+    LengthGreater(Value, usize),
+    LengthLess(Value, usize),
+    IsType(Value, String), // null bool number integer string array object
+    In(Value, Vec<Value>),
+    Between(Value, Value, Value), // 闭区间
+    Not(Box<SelectNode>),
     And(Vec<SelectNode>),
     Or(Vec<SelectNode>),
 }
@@ -50,6 +64,13 @@ impl Display for SelectCfg {
     }
 }
 impl SelectNode {
+    //运行时解析条件中的${{xxx}}
+    pub fn resolve(&self, lookup: &dyn Fn(&str) -> Option<Value>) -> anyhow::Result<SelectNode> {
+        let mut val = serde_json::to_value(self)?;
+        string::render_template_value(&mut val, lookup);
+        let node = serde_json::from_value(val)?;
+        Ok(node)
+    }
     pub fn number_value_compare(
         left: Value,
         right: Value,
@@ -152,51 +173,182 @@ impl SelectNode {
                 }
                 return anyhow::anyhow!("select.Contain[{a:?}] no support type[{b:?}]").err();
             }
-            SelectNode::Empty(a) => {
-                if !a.is_null() {
-                    return Ok(false);
+            SelectNode::Empty(a) => Ok(Self::is_empty(&a)),
+            SelectNode::NonEmpty(a) => Ok(!Self::is_empty(&a)),
+            SelectNode::StartsWith(a, b) => match (a, b) {
+                (Value::String(a), Value::String(b)) => Ok(a.starts_with(b.as_str())),
+                (a, b) => anyhow::anyhow!("select.StartsWith[{a:?}] no support type[{b:?}]").err(),
+            },
+            SelectNode::EndsWith(a, b) => match (a, b) {
+                (Value::String(a), Value::String(b)) => Ok(a.ends_with(b.as_str())),
+                (a, b) => anyhow::anyhow!("select.EndsWith[{a:?}] no support type[{b:?}]").err(),
+            },
+            SelectNode::EqualIgnoreCase(a, b) => match (a, b) {
+                (Value::String(a), Value::String(b)) => Ok(a.to_lowercase() == b.to_lowercase()),
+                (a, b) => Ok(a == b),
+            },
+            SelectNode::Regex(a, pattern) => {
+                let re = match Regex::new(pattern.as_str()) {
+                    Ok(o) => o,
+                    Err(e) => return anyhow::anyhow!("select.Regex[{pattern}] error:{e}").err(),
+                };
+                match a {
+                    Value::String(ref s) => Ok(re.is_match(s)),
+                    _ => anyhow::anyhow!("select.Regex no support type[{a:?}]").err(),
                 }
-                return Ok(true);
             }
-            SelectNode::NonEmpty(a) => {
-                if a.is_null() {
-                    return Ok(false);
-                }
-
-                return Ok(true);
+            SelectNode::LengthEqual(a, n) => Ok(Self::length(&a)? == n),
+            SelectNode::LengthGreater(a, n) => Ok(Self::length(&a)? > n),
+            SelectNode::LengthLess(a, n) => Ok(Self::length(&a)? < n),
+            SelectNode::IsType(a, ty) => {
+                let res = match ty.as_str() {
+                    "null" => a.is_null(),
+                    "bool" | "boolean" => a.is_boolean(),
+                    "number" => a.is_number(),
+                    "integer" => a.is_i64() || a.is_u64(),
+                    "string" => a.is_string(),
+                    "array" => a.is_array(),
+                    "object" => a.is_object(),
+                    _ => return anyhow::anyhow!("select.IsType unknown type[{ty}]").err(),
+                };
+                Ok(res)
+            }
+            SelectNode::In(a, list) => Ok(list.contains(&a)),
+            SelectNode::Between(a, low, high) => {
+                let ge = Self::number_value_compare(
+                    a.clone(),
+                    low,
+                    SelectNode::GreaterEqual(Value::Null, Value::Null),
+                )?;
+                let le = Self::number_value_compare(
+                    a,
+                    high,
+                    SelectNode::LessEqual(Value::Null, Value::Null),
+                )?;
+                Ok(ge && le)
             }
             _ => return anyhow::anyhow!("select.SelectCond[{self:?}] no support calc").err(),
         }
     }
+    fn is_empty(a: &Value) -> bool {
+        match a {
+            Value::Null => true,
+            Value::String(s) => s.is_empty(),
+            Value::Array(list) => list.is_empty(),
+            Value::Object(obj) => obj.is_empty(),
+            _ => false,
+        }
+    }
+    fn length(a: &Value) -> anyhow::Result<usize> {
+        match a {
+            Value::String(s) => Ok(s.chars().count()),
+            Value::Array(list) => Ok(list.len()),
+            Value::Object(obj) => Ok(obj.len()),
+            _ => anyhow::anyhow!("select.Length no support type[{a:?}]").err(),
+        }
+    }
     pub fn generate_result(self) -> anyhow::Result<bool> {
-        match self {
-            SelectNode::And(list) => {
-                if list.len() < 2 {
-                    return anyhow::anyhow!("select.SelectNode[and].cond must >= two").err();
-                }
-                for i in list {
-                    if !i.generate_result()? {
-                        return Ok(false);
-                    }
+        Ok(self.explain()?.result)
+    }
+    //形如 Greater(8, 9)
+    fn label(&self) -> String {
+        let val = serde_json::to_value(self).unwrap_or(Value::Null);
+        let (name, args) = match val {
+            Value::Object(obj) => match obj.into_iter().next() {
+                Some((k, Value::Array(list))) => (k, list),
+                Some((k, v)) => (k, vec![v]),
+                None => return String::new(),
+            },
+            Value::String(k) => (k, vec![]),
+            _ => return String::new(),
+        };
+        let name = name
+            .split('_')
+            .map(|x| {
+                let mut cs = x.chars();
+                match cs.next() {
+                    Some(c) => c.to_uppercase().chain(cs).collect::<String>(),
+                    None => String::new(),
                 }
-                Ok(true)
+            })
+            .collect::<String>();
+        let args = args.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        format!("{}({})", name, args.join(", "))
+    }
+    //计算结果并记录每个子条件的实际操作数，and/or短路后的条件不再记录
+    pub fn explain(self) -> anyhow::Result<SelectExplain> {
+        let (expr, list, is_and) = match self {
+            SelectNode::Not(node) => {
+                let child = node.explain()?;
+                return Ok(SelectExplain {
+                    expr: "Not".into(),
+                    result: !child.result,
+                    children: vec![child],
+                });
             }
-            SelectNode::Or(list) => {
-                if list.len() < 2 {
-                    return anyhow::anyhow!("select.SelectNode[or].cond must >= two").err();
-                }
-                for i in list {
-                    if i.generate_result()? {
-                        return Ok(true);
-                    }
-                }
-                Ok(false)
+            SelectNode::And(list) => ("And", list, true),
+            SelectNode::Or(list) => ("Or", list, false),
+            _ => {
+                return Ok(SelectExplain {
+                    expr: self.label(),
+                    result: self.calc()?,
+                    children: vec![],
+                })
+            }
+        };
+        if list.is_empty() {
+            return anyhow::anyhow!("select.SelectNode[{}].cond is empty", expr.to_lowercase())
+                .err();
+        }
+        let mut children = vec![];
+        let mut result = is_and;
+        for i in list {
+            let child = i.explain()?;
+            let stop = child.result != is_and;
+            children.push(child);
+            if stop {
+                result = !is_and;
+                break;
             }
-            _ => return self.calc(),
         }
+        Ok(SelectExplain {
+            expr: expr.into(),
+            result,
+            children,
+        })
     }
 }
 
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SelectExplain {
+    pub expr: String,
+    pub result: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<SelectExplain>,
+}
+
+impl Display for SelectExplain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.children.is_empty() {
+            return write!(f, "{}={}", self.expr, self.result);
+        }
+        let children = self
+            .children
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}({})={}", self.expr, children.join(", "), self.result)
+    }
+}
+
+//flow_select开启explain时，每次判断后通过env发出；判断过程始终以 节点名#explain 写入vars
+#[derive(Default, Debug, Clone)]
+pub struct SelectExplainEvent {
+    pub node: String,
+    pub explain: SelectExplain,
+}
+
 #[async_trait::async_trait]
 impl JsonServiceExt<SelectCfg, bool> for Select {
     async fn output(&self, out: bool) -> anyhow::Result<Output> {
@@ -204,7 +356,8 @@ impl JsonServiceExt<SelectCfg, bool> for Select {
     }
     async fn call(&self, ctx: Ctx, cfg: SelectCfg, se: ServiceEntity) -> anyhow::Result<bool> {
         //进行判断
-        let res = cfg.conditions.generate_result()?;
+        let explain = cfg.conditions.explain()?;
+        let res = explain.result;
         //修改plan
         let node = se.node_name;
         if res {
@@ -216,14 +369,30 @@ impl JsonServiceExt<SelectCfg, bool> for Select {
                 p.set_to(node.as_str(), cfg.false_to_nodes);
             });
         }
+        //记录本次运行中该节点的判断过程，如 ${{select#explain}}
+        ctx.insert_var(
+            format!("{}#explain", node),
+            Output::value(serde_json::to_value(&explain)?),
+        )
+        .await;
+        if cfg.explain {
+            let event = SelectExplainEvent { node, explain };
+            if let Err(e) = ctx.get_env().feedback_ext(event).await {
+                wd_log::log_field("error", e).warn("flow_select feedback explain failed");
+            }
+        }
         Ok(res)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::service::flow::{SelectCfg, SelectNode};
-    use serde_json::Value;
+    use crate::core::{CabinetEnv, CtxSerdeExt, EngineRT, EnvExt, JsonInput};
+    use crate::plan::graph::{Graph, GraphNode};
+    use crate::service::ext::ServiceLoaderWrap;
+    use crate::service::flow::{SelectCfg, SelectExplain, SelectExplainEvent, SelectNode};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
 
     #[test]
     fn select_config() {
@@ -235,7 +404,104 @@ mod test {
             conditions: SelectNode::Greater(Value::from(123), Value::from(456)),
             true_to_nodes: vec!["A".into()],
             false_to_nodes: vec!["B".into()],
+            explain: false,
         };
         println!("{}", cfg)
     }
+
+    #[test]
+    fn select_node_calc() {
+        let cases = vec![
+            (SelectNode::Empty(json!("")), true),
+            (SelectNode::Empty(json!({})), true),
+            (SelectNode::NonEmpty(json!([1])), true),
+            (SelectNode::StartsWith(json!("refund: yes"), json!("refund")), true),
+            (SelectNode::EndsWith(json!("a.pdf"), json!(".txt")), false),
+            (SelectNode::EqualIgnoreCase(json!("Refund"), json!("REFUND")), true),
+            (SelectNode::Regex(json!("order-123"), r"^order-\d+$".into()), true),
+            (SelectNode::LengthGreater(json!("hello"), 3), true),
+            (SelectNode::LengthEqual(json!([1, 2]), 2), true),
+            (SelectNode::LengthLess(json!({"a":1}), 1), false),
+            (SelectNode::IsType(json!(1), "integer".into()), true),
+            (SelectNode::IsType(json!("1"), "number".into()), false),
+            (SelectNode::In(json!("b"), vec![json!("a"), json!("b")]), true),
+            (SelectNode::Between(json!(0.5), json!(0), json!(1)), true),
+            (SelectNode::Between(json!(2), json!(0), json!(1)), false),
+            (SelectNode::Not(Box::new(SelectNode::Empty(json!(null)))), false),
+            (SelectNode::And(vec![SelectNode::None]), true),
+            (SelectNode::Or(vec![SelectNode::Empty(json!(1))]), false),
+        ];
+        for (node, expect) in cases {
+            let text = node.to_string();
+            assert_eq!(node.generate_result().unwrap(), expect, "{}", text);
+        }
+        assert!(SelectNode::And(vec![]).generate_result().is_err());
+        assert!(SelectNode::Regex(json!("a"), "(".into())
+            .generate_result()
+            .is_err());
+
+        let node: SelectNode =
+            serde_json::from_str(r#"{"not":{"in":["${{x}}",["a","b"]]}}"#).unwrap();
+        let node = node.resolve(&|_| Some(json!("c"))).unwrap();
+        assert!(node.generate_result().unwrap());
+
+        let node: SelectNode = serde_json::from_str(
+            r#"{"or":[{"greater":["${{n}}",9]},{"and":[{"empty":"${{tag}}"},{"less":["${{n}}",5]}]}]}"#,
+        )
+        .unwrap();
+        let explain = node
+            .resolve(&|p| Some(if p == "n" { json!(8) } else { json!("") }))
+            .unwrap()
+            .explain()
+            .unwrap();
+        assert_eq!(
+            explain.to_string(),
+            r#"Or(Greater(8, 9)=false, And(Empty("")=true, Less(8, 5)=false)=false)=false"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_select_explain() {
+        let rt = EngineRT::default()
+            .set_service_loader(ServiceLoaderWrap::default())
+            .build();
+        let cfg = |cond: Value, to: &str, explain: bool| {
+            JsonInput::default().set_default_json(json!({
+                "conditions": cond,
+                "true_to_nodes": [to],
+                "explain": explain,
+            }))
+        };
+        //两个select依次判断，各自的判断过程都保留在vars中，只有second发出事件
+        let plan = Graph::default()
+            .node(("start", r#"{"service_name":"start","config":{"transform_rule":{"n":{"quote":"n"}}}}"#))
+            .node(GraphNode::new("first").set_service_entity_json("flow_select", cfg(json!({"greater":["${{start.n}}", 5]}), "second", false)))
+            .node(GraphNode::new("second").set_service_entity_json("flow_select", cfg(json!({"and":[{"less":["${{start.n}}", 9]},{"empty":""}]}), "end", true)))
+            .node(("end", r#"{"service_name":"end","config":{"transform_rule":{"first":{"quote":"first"},"second":{"quote":"second"},"first_explain":{"quote":"[\"first#explain\"]"},"second_explain":{"quote":"second#explain"}}}}"#))
+            .edges([("start", "first"), ("first", "second"), ("second", "end")])
+            .check()
+            .unwrap();
+
+        let env = Arc::new(CabinetEnv::new());
+        let res: Value = rt
+            .ctx(plan)
+            .set_env(env.clone())
+            .serde_run(json!({"n": 8}))
+            .await
+            .unwrap();
+        assert_eq!(res["first"], true);
+        assert_eq!(res["second"], true);
+        assert_eq!(
+            res["first_explain"],
+            json!({"expr":"Greater(8, 5)","result":true})
+        );
+        let second: SelectExplain =
+            serde_json::from_value(res["second_explain"].clone()).unwrap();
+        assert_eq!(
+            second.to_string(),
+            r#"And(Less(8, 9)=true, Empty("")=true)=true"#
+        );
+        let event = env.watch_ext::<SelectExplainEvent>().await.unwrap().unwrap();
+        assert_eq!(event.node, "second");
+    }
 }
@@ -0,0 +1,123 @@
+use crate::core::{Ctx, EnvExt, JsonServiceExt, ServiceEntity, Usage};
+use crate::service::llm::{
+    ChatMessage, ChatModel, ChatRequest, ChatResponse, OpenAiChatModel, OpenAiConfig,
+};
+use std::sync::Arc;
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ChatCompletionCfg {
+    #[serde(flatten)]
+    pub request: ChatRequest,
+    //不为空时分别作为第一条system消息和最后一条user消息
+    pub system: String,
+    pub prompt: String,
+}
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ChatCompletionOutput {
+    //message.content的快捷引用
+    pub content: String,
+    #[serde(flatten)]
+    pub response: ChatResponse,
+}
+
+//stream为true时每个增量发送到env中的ChatDeltaSender
+#[derive(Default, Debug, Clone)]
+pub struct ChatDeltaEvent {
+    pub node: String,
+    pub delta: String,
+}
+
+//通过CabinetEnv::set_resource(ChatDeltaSender(tx))放入env
+#[derive(Debug, Clone)]
+pub struct ChatDeltaSender(pub tokio::sync::mpsc::UnboundedSender<ChatDeltaEvent>);
+
+//模型优先级：new指定 > env中的OpenAiConfig > OPENAI_*环境变量
+#[derive(Default)]
+pub struct ChatCompletion {
+    pub model: Option<Arc<dyn ChatModel>>,
+}
+
+impl ChatCompletion {
+    pub fn new<M: ChatModel + 'static>(model: M) -> Self {
+        Self {
+            model: Some(Arc::new(model)),
+        }
+    }
+    fn load_model(&self, ctx: &Ctx) -> Arc<dyn ChatModel> {
+        if let Some(ref m) = self.model {
+            return m.clone();
+        }
+        let cfg = match ctx.get_env().resource_ext::<OpenAiConfig>() {
+            Some(cfg) => cfg.as_ref().clone(),
+            None => OpenAiConfig::from_env(),
+        };
+        Arc::new(OpenAiChatModel::new(cfg))
+    }
+}
+
+#[async_trait::async_trait]
+impl JsonServiceExt<ChatCompletionCfg, ChatCompletionOutput> for ChatCompletion {
+    fn usage(&self, out: &ChatCompletionOutput) -> Option<Usage> {
+        out.response.usage.as_ref().map(|u| Usage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+            total_tokens: u.total_tokens,
+            cost: u.cost,
+        })
+    }
+    async fn call(
+        &self,
+        ctx: Ctx,
+        cfg: ChatCompletionCfg,
+        se: ServiceEntity,
+    ) -> anyhow::Result<ChatCompletionOutput> {
+        let mut req = cfg.request;
+        if !cfg.system.is_empty() {
+            req.messages.insert(0, ChatMessage::system(cfg.system));
+        }
+        if !cfg.prompt.is_empty() {
+            req.messages.push(ChatMessage::user(cfg.prompt));
+        }
+        let model = self.load_model(&ctx);
+        let response = if req.stream {
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+            let sender = ctx.get_env().resource_ext::<ChatDeltaSender>();
+            let node = se.node_name.clone();
+            let forward = async move {
+                while let Some(delta) = rx.recv().await {
+                    let sender = match sender {
+                        Some(ref s) => s,
+                        None => continue,
+                    };
+                    let event = ChatDeltaEvent {
+                        node: node.clone(),
+                        delta,
+                    };
+                    //接收端关闭后丢弃增量，不影响模型调用
+                    let _ = sender.0.send(event);
+                }
+            };
+            let (resp, _) = tokio::join!(model.chat_stream(req, tx), forward);
+            resp
+        } else {
+            model.chat(req).await
+        };
+        let response = match response {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(anyhow::anyhow!(
+                    "Node[{}] chat completion error:{}",
+                    se.node_name,
+                    e
+                ))
+            }
+        };
+        Ok(ChatCompletionOutput {
+            content: response.message.content.clone(),
+            response,
+        })
+    }
+}
@@ -0,0 +1,7 @@
+mod chat;
+mod model;
+mod openai;
+
+pub use chat::*;
+pub use model::*;
+pub use openai::*;
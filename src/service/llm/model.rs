@@ -0,0 +1,150 @@
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+
+fn null_as_default<'de, D: Deserializer<'de>, T: Default + Deserialize<'de>>(
+    d: D,
+) -> Result<T, D::Error> {
+    Ok(Option::<T>::deserialize(d)?.unwrap_or_default())
+}
+
+#[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ChatMessage {
+    pub role: String,
+    //带tool_calls的消息content为null
+    #[serde(deserialize_with = "null_as_default")]
+    pub content: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new<R: Into<String>, C: Into<String>>(role: R, content: C) -> Self {
+        Self {
+            role: role.into(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+    pub fn system<C: Into<String>>(content: C) -> Self {
+        Self::new("system", content)
+    }
+    pub fn user<C: Into<String>>(content: C) -> Self {
+        Self::new("user", content)
+    }
+    pub fn assistant<C: Into<String>>(content: C) -> Self {
+        Self::new("assistant", content)
+    }
+    pub fn tool<I: Into<String>, C: Into<String>>(tool_call_id: I, content: C) -> Self {
+        Self {
+            role: "tool".into(),
+            content: content.into(),
+            tool_call_id: Some(tool_call_id.into()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+#[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct FunctionCall {
+    pub name: String,
+    //json字符串
+    pub arguments: String,
+}
+
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ToolDefinition {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
+}
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    //json schema
+    pub parameters: Value,
+}
+
+impl ToolDefinition {
+    pub fn function<N: Into<String>, D: Into<String>>(
+        name: N,
+        description: D,
+        parameters: Value,
+    ) -> Self {
+        Self {
+            kind: "function".into(),
+            function: FunctionDefinition {
+                name: name.into(),
+                description: description.into(),
+                parameters,
+            },
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ChatRequest {
+    //为空时使用模型配置的默认值
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    pub stream: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ChatUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    //由模型的价格配置计算，未配置时为0
+    pub cost: f64,
+}
+
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ChatResponse {
+    pub model: String,
+    pub message: ChatMessage,
+    pub finish_reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ChatUsage>,
+}
+
+#[async_trait::async_trait]
+pub trait ChatModel: Send + Sync {
+    async fn chat(&self, req: ChatRequest) -> anyhow::Result<ChatResponse>;
+    //流式输出：每个增量文本发送到delta，返回完整结果
+    async fn chat_stream(
+        &self,
+        req: ChatRequest,
+        delta: UnboundedSender<String>,
+    ) -> anyhow::Result<ChatResponse> {
+        let resp = self.chat(req).await?;
+        let _ = delta.send(resp.message.content.clone());
+        Ok(resp)
+    }
+}
@@ -0,0 +1,388 @@
+use crate::service::llm::{ChatMessage, ChatModel, ChatRequest, ChatResponse, ChatUsage, ToolCall};
+use serde_json::{json, Value};
+use tokio::sync::mpsc::UnboundedSender;
+use wd_tools::http::{Http, Method, Response, Url};
+use wd_tools::PFErr;
+
+//OpenAI兼容接口的配置，可以通过CabinetEnv::set_resource放入env
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct OpenAiConfig {
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    //每百万token的价格，用于计算usage.cost
+    pub prompt_price: f64,
+    pub completion_price: f64,
+}
+
+impl Default for OpenAiConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://api.openai.com".into(),
+            api_key: String::new(),
+            model: "gpt-4o-mini".into(),
+            prompt_price: 0.0,
+            completion_price: 0.0,
+        }
+    }
+}
+
+impl OpenAiConfig {
+    //OPENAI_BASE_URL OPENAI_API_KEY OPENAI_MODEL
+    pub fn from_env() -> Self {
+        let mut cfg = Self::default();
+        if let Ok(s) = std::env::var("OPENAI_BASE_URL") {
+            cfg.base_url = s;
+        }
+        if let Ok(s) = std::env::var("OPENAI_API_KEY") {
+            cfg.api_key = s;
+        }
+        if let Ok(s) = std::env::var("OPENAI_MODEL") {
+            cfg.model = s;
+        }
+        cfg
+    }
+    pub fn set_base_url<S: Into<String>>(mut self, s: S) -> Self {
+        self.base_url = s.into();
+        self
+    }
+    pub fn set_api_key<S: Into<String>>(mut self, s: S) -> Self {
+        self.api_key = s.into();
+        self
+    }
+    pub fn set_model<S: Into<String>>(mut self, s: S) -> Self {
+        self.model = s.into();
+        self
+    }
+    pub fn set_price(mut self, prompt_price: f64, completion_price: f64) -> Self {
+        self.prompt_price = prompt_price;
+        self.completion_price = completion_price;
+        self
+    }
+    pub fn cost(&self, usage: &ChatUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt_price
+            + usage.completion_tokens as f64 * self.completion_price)
+            / 1_000_000.0
+    }
+    //兼容 http://host 和 http://host/v1 两种写法
+    pub fn endpoint(&self) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        if base.ends_with("/v1") {
+            format!("{}/chat/completions", base)
+        } else {
+            format!("{}/v1/chat/completions", base)
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct OpenAiChatModel {
+    pub cfg: OpenAiConfig,
+}
+
+impl OpenAiChatModel {
+    pub fn new(cfg: OpenAiConfig) -> Self {
+        Self { cfg }
+    }
+    fn body(&self, mut req: ChatRequest) -> anyhow::Result<Value> {
+        if req.model.is_empty() {
+            req.model = self.cfg.model.clone();
+        }
+        let mut body = serde_json::to_value(req)?;
+        if body["stream"] == json!(true) {
+            body["stream_options"] = json!({"include_usage": true});
+        }
+        Ok(body)
+    }
+    async fn send(&self, body: Value) -> anyhow::Result<Response> {
+        let url = Url::parse(self.cfg.endpoint().as_str())?;
+        let mut http = Http::new(Method::POST, url)?.header("Content-Type", "application/json");
+        if !self.cfg.api_key.is_empty() {
+            http = http.header("Authorization", format!("Bearer {}", self.cfg.api_key));
+        }
+        let resp: Response = http.body(serde_json::to_vec(&body)?).into_send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            return anyhow::anyhow!("chat completion http status[{}]: {}", status, text).err();
+        }
+        Ok(resp)
+    }
+    //合并一个流式分片中的delta
+    fn merge_chunk(resp: &mut ChatResponse, chunk: &Value, delta: &UnboundedSender<String>) {
+        if let Some(s) = chunk["model"].as_str() {
+            resp.model = s.to_string();
+        }
+        if chunk["usage"].is_object() {
+            resp.usage = serde_json::from_value(chunk["usage"].clone()).ok();
+        }
+        let choice = &chunk["choices"][0];
+        if let Some(s) = choice["finish_reason"].as_str() {
+            resp.finish_reason = s.to_string();
+        }
+        let d = &choice["delta"];
+        if let Some(s) = d["role"].as_str() {
+            resp.message.role = s.to_string();
+        }
+        if let Some(s) = d["content"].as_str() {
+            resp.message.content.push_str(s);
+            let _ = delta.send(s.to_string());
+        }
+        //tool_calls按index分片到达，arguments需要拼接
+        for tc in d["tool_calls"].as_array().into_iter().flatten() {
+            let i = tc["index"].as_u64().unwrap_or(0) as usize;
+            let calls = &mut resp.message.tool_calls;
+            while calls.len() <= i {
+                calls.push(ToolCall {
+                    kind: "function".into(),
+                    ..Default::default()
+                });
+            }
+            if let Some(s) = tc["id"].as_str() {
+                calls[i].id = s.to_string();
+            }
+            if let Some(s) = tc["function"]["name"].as_str() {
+                calls[i].function.name.push_str(s);
+            }
+            if let Some(s) = tc["function"]["arguments"].as_str() {
+                calls[i].function.arguments.push_str(s);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatModel for OpenAiChatModel {
+    async fn chat(&self, mut req: ChatRequest) -> anyhow::Result<ChatResponse> {
+        req.stream = false;
+        let resp = self.send(self.body(req)?).await?;
+        let val: Value = resp.json().await?;
+        let choice = &val["choices"][0];
+        if choice.is_null() {
+            return anyhow::anyhow!("chat completion response has no choices: {}", val).err();
+        }
+        let usage = serde_json::from_value::<ChatUsage>(val["usage"].clone())
+            .ok()
+            .map(|mut u| {
+                u.cost = self.cfg.cost(&u);
+                u
+            });
+        Ok(ChatResponse {
+            model: val["model"].as_str().unwrap_or_default().to_string(),
+            message: serde_json::from_value::<ChatMessage>(choice["message"].clone())?,
+            finish_reason: choice["finish_reason"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            usage,
+        })
+    }
+
+    async fn chat_stream(
+        &self,
+        mut req: ChatRequest,
+        delta: UnboundedSender<String>,
+    ) -> anyhow::Result<ChatResponse> {
+        req.stream = true;
+        let mut resp = self.send(self.body(req)?).await?;
+        let mut result = ChatResponse {
+            message: ChatMessage::assistant(""),
+            ..Default::default()
+        };
+        let mut buf: Vec<u8> = vec![];
+        //SSE：按行解析 data: {...}，以 data: [DONE] 结束
+        'read: while let Some(chunk) = resp.chunk().await? {
+            buf.extend_from_slice(&chunk);
+            while let Some(i) = buf.iter().position(|x| *x == b'\n') {
+                let line = buf.drain(..=i).collect::<Vec<u8>>();
+                let line = String::from_utf8_lossy(&line);
+                let data = match line.trim().strip_prefix("data:") {
+                    Some(s) => s.trim(),
+                    None => continue,
+                };
+                if data == "[DONE]" {
+                    break 'read;
+                }
+                let val: Value = match serde_json::from_str(data) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        return anyhow::anyhow!(
+                            "chat completion stream invalid chunk[{}]: {}",
+                            data,
+                            e
+                        )
+                        .err()
+                    }
+                };
+                Self::merge_chunk(&mut result, &val, &delta);
+            }
+        }
+        if let Some(ref mut u) = result.usage {
+            u.cost = self.cfg.cost(u);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{CabinetEnv, CtxSerdeExt, EngineRT, JsonInput};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::ServiceLoaderWrap;
+    use crate::service::llm::{
+        ChatDeltaSender, ChatMessage, ChatModel, ChatRequest, OpenAiChatModel, OpenAiConfig,
+    };
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    //本地模拟 /v1/chat/completions，记录收到的请求体
+    async fn mock_server(requests: Arc<Mutex<Vec<Value>>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut conn, _)) = listener.accept().await {
+                let mut buf = vec![];
+                let mut tmp = [0u8; 1024];
+                let body = loop {
+                    let n = conn.read(&mut tmp).await.unwrap();
+                    buf.extend_from_slice(&tmp[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some(i) = text.find("\r\n\r\n") {
+                        let len = text[..i]
+                            .lines()
+                            .find_map(|x| {
+                                x.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|x| x.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if buf.len() >= i + 4 + len {
+                            break serde_json::from_slice::<Value>(&buf[i + 4..i + 4 + len])
+                                .unwrap();
+                        }
+                    }
+                };
+                let stream = body["stream"] == json!(true);
+                requests.lock().unwrap().push(body);
+                let resp = if stream {
+                    let chunks = [
+                        json!({"model":"mock","choices":[{"delta":{"role":"assistant","content":"Hel"}}]}),
+                        json!({"model":"mock","choices":[{"delta":{"content":"lo"}}]}),
+                        json!({"model":"mock","choices":[{"delta":{},"finish_reason":"stop"}]}),
+                        json!({"model":"mock","choices":[],"usage":{"prompt_tokens":5,"completion_tokens":2,"total_tokens":7}}),
+                    ];
+                    let mut s = chunks
+                        .iter()
+                        .map(|x| format!("data: {}\n\n", x))
+                        .collect::<String>();
+                    s.push_str("data: [DONE]\n\n");
+                    format!("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}", s)
+                } else {
+                    let s = json!({
+                        "model":"mock",
+                        "choices":[{"message":{"role":"assistant","content":null,"tool_calls":[{"id":"c1","type":"function","function":{"name":"search","arguments":"{\"q\":\"rust\"}"}}]},"finish_reason":"tool_calls"}],
+                        "usage":{"prompt_tokens":10,"completion_tokens":3,"total_tokens":13}
+                    })
+                    .to_string();
+                    format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", s.len(), s)
+                };
+                conn.write_all(resp.as_bytes()).await.unwrap();
+                conn.shutdown().await.unwrap();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_openai_chat() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let base_url = mock_server(requests.clone()).await;
+        let cfg = OpenAiConfig::default()
+            .set_base_url(base_url)
+            .set_api_key("sk-test")
+            .set_model("mock")
+            .set_price(1000.0, 2000.0);
+
+        //流式
+        let model = OpenAiChatModel::new(cfg.clone());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let req = ChatRequest {
+            messages: vec![ChatMessage::user("hi")],
+            ..Default::default()
+        };
+        let resp = model.chat_stream(req, tx).await.unwrap();
+        assert_eq!(resp.message.content, "Hello");
+        assert_eq!(resp.finish_reason, "stop");
+        assert_eq!(resp.usage.map(|x| x.total_tokens), Some(7));
+        let mut deltas = vec![];
+        while let Some(s) = rx.recv().await {
+            deltas.push(s);
+        }
+        assert_eq!(deltas, vec!["Hel", "lo"]);
+
+        //通过env配置，经由chat_completion节点调用
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let env = Arc::new(
+            CabinetEnv::new()
+                .set_resource(cfg)
+                .set_resource(ChatDeltaSender(tx)),
+        );
+        let rt = EngineRT::default()
+            .set_service_loader(ServiceLoaderWrap::default())
+            .build();
+        let plan = Pipeline::default()
+            .step((
+                "chat_completion",
+                JsonInput::default()
+                    .set_default_json(json!({"system":"be short","prompt":"${{start.q}}"})),
+            ))
+            .check()
+            .unwrap();
+        let res: Value = rt
+            .ctx(plan)
+            .set_env(env.clone())
+            .serde_run(json!({"q":"search rust"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            res["message"]["tool_calls"][0]["function"]["name"],
+            "search"
+        );
+        assert_eq!(res["finish_reason"], "tool_calls");
+        assert_eq!(res["usage"]["total_tokens"], 13);
+        assert_eq!(res["usage"]["cost"], 0.016);
+
+        let body = requests.lock().unwrap().pop().unwrap();
+        assert_eq!(body["model"], "mock");
+        assert_eq!(
+            body["messages"],
+            json!([{"role":"system","content":"be short"},{"role":"user","content":"search rust"}])
+        );
+        assert!(rx.try_recv().is_err());
+
+        //流式节点的每个增量都送到ChatDeltaSender
+        let plan = Pipeline::default()
+            .step((
+                "chat_completion",
+                JsonInput::default().set_default_json(json!({"prompt":"hi","stream":true})),
+            ))
+            .check()
+            .unwrap();
+        let res: Value = rt
+            .ctx(plan)
+            .set_env(env.clone())
+            .serde_run(json!({}))
+            .await
+            .unwrap();
+        assert_eq!(res["content"], "Hello");
+        let mut deltas = vec![];
+        while let Ok(e) = rx.try_recv() {
+            assert_eq!(e.node, "chat_completion");
+            deltas.push(e.delta);
+        }
+        assert_eq!(deltas, vec!["Hel", "lo"]);
+    }
+}
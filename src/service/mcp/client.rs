@@ -0,0 +1,248 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{oneshot, Mutex};
+use wd_tools::PFErr;
+
+pub const MCP_PROTOCOL_VERSION: &str = "2024-11-05";
+pub const MCP_DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct McpTool {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+}
+
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct McpToolResult {
+    pub content: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+    pub is_error: bool,
+}
+
+impl McpToolResult {
+    //拼接所有text类型的content
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter(|x| x["type"] == "text")
+            .filter_map(|x| x["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+//MCP客户端：基于换行分隔的JSON-RPC 2.0，stdio或任意双工流
+pub struct McpClient {
+    writer: Mutex<Box<dyn AsyncWrite + Send + Unpin>>,
+    pending: Pending,
+    //read_loop退出后置为true，之后的请求直接失败
+    closed: Arc<AtomicBool>,
+    id: AtomicU64,
+    timeout: Duration,
+    //stdio方式启动的子进程，随client一起释放
+    child: Option<Mutex<Child>>,
+}
+
+impl McpClient {
+    pub fn new<R, W>(reader: R, writer: W) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        tokio::spawn(Self::read_loop(reader, pending.clone(), closed.clone()));
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+            pending,
+            closed,
+            id: AtomicU64::new(1),
+            timeout: MCP_DEFAULT_TIMEOUT,
+            child: None,
+        }
+    }
+    //单个请求等待响应的超时时间
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    //启动本地MCP server子进程并完成initialize握手
+    pub async fn spawn<S: AsRef<str>>(command: &str, args: &[S]) -> anyhow::Result<Self> {
+        let mut child = Command::new(command)
+            .args(args.iter().map(|x| x.as_ref()))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()?;
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(i), Some(o)) => (i, o),
+            _ => return anyhow::anyhow!("mcp server[{}] stdio not available", command).err(),
+        };
+        let mut client = Self::new(stdout, stdin);
+        client.child = Some(Mutex::new(child));
+        client.initialize().await?;
+        Ok(client)
+    }
+    async fn read_loop<R: AsyncRead + Send + Unpin>(
+        reader: R,
+        pending: Pending,
+        closed: Arc<AtomicBool>,
+    ) {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let msg = match serde_json::from_str::<Value>(line.as_str()) {
+                Ok(v) => v,
+                Err(e) => {
+                    wd_log::log_field("line", line)
+                        .field("error", e)
+                        .warn("mcp client invalid message");
+                    continue;
+                }
+            };
+            //只处理响应，server发来的通知忽略
+            let id = match msg["id"].as_u64() {
+                Some(id) if msg.get("method").is_none() => id,
+                _ => continue,
+            };
+            if let Some(tx) = pending.lock().await.remove(&id) {
+                let _ = tx.send(msg);
+            }
+        }
+        //连接断开，等待中的请求全部失败
+        closed.store(true, Ordering::SeqCst);
+        pending.lock().await.clear();
+    }
+    async fn write(&self, msg: Value) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(&msg)?;
+        line.push(b'\n');
+        let mut w = self.writer.lock().await;
+        w.write_all(&line).await?;
+        w.flush().await?;
+        Ok(())
+    }
+    pub async fn request(&self, method: &str, params: Value) -> anyhow::Result<Value> {
+        let id = self.id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+        if self.closed.load(Ordering::SeqCst) {
+            self.pending.lock().await.remove(&id);
+            return anyhow::anyhow!("mcp request[{}] connection closed", method).err();
+        }
+        let msg = json!({"jsonrpc":"2.0","id":id,"method":method,"params":params});
+        if let Err(e) = self.write(msg).await {
+            self.pending.lock().await.remove(&id);
+            return Err(e);
+        }
+        let mut resp = match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(o)) => o,
+            Ok(Err(_)) => {
+                return anyhow::anyhow!("mcp request[{}] connection closed", method).err()
+            }
+            Err(_) => {
+                self.pending.lock().await.remove(&id);
+                return anyhow::anyhow!("mcp request[{}] timeout after {:?}", method, self.timeout)
+                    .err();
+            }
+        };
+        if let Some(e) = resp.get("error") {
+            return anyhow::anyhow!(
+                "mcp request[{}] error[{}]: {}",
+                method,
+                e["code"],
+                e["message"].as_str().unwrap_or_default()
+            )
+            .err();
+        }
+        Ok(resp["result"].take())
+    }
+    pub async fn notify(&self, method: &str, params: Value) -> anyhow::Result<()> {
+        self.write(json!({"jsonrpc":"2.0","method":method,"params":params}))
+            .await
+    }
+    //返回server的initialize结果(serverInfo、capabilities)
+    pub async fn initialize(&self) -> anyhow::Result<Value> {
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": MCP_PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "art", "version": env!("CARGO_PKG_VERSION")},
+                }),
+            )
+            .await?;
+        self.notify("notifications/initialized", json!({})).await?;
+        Ok(result)
+    }
+    pub async fn list_tools(&self) -> anyhow::Result<Vec<McpTool>> {
+        let mut tools = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match cursor {
+                Some(ref c) => json!({"cursor": c}),
+                None => json!({}),
+            };
+            let mut result = self.request("tools/list", params).await?;
+            let list: Vec<McpTool> = serde_json::from_value(result["tools"].take())?;
+            tools.extend(list);
+            match result["nextCursor"].as_str() {
+                Some(c) if !c.is_empty() => cursor = Some(c.to_string()),
+                _ => return Ok(tools),
+            }
+        }
+    }
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> anyhow::Result<McpToolResult> {
+        let result = self
+            .request("tools/call", json!({"name": name, "arguments": arguments}))
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+    pub async fn close(&self) -> anyhow::Result<()> {
+        self.writer.lock().await.shutdown().await?;
+        if let Some(ref child) = self.child {
+            child.lock().await.kill().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::service::mcp::McpClient;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_mcp_client_timeout() {
+        //对端从不响应
+        let (client_side, server_side) = tokio::io::duplex(4096);
+        let (r, w) = tokio::io::split(client_side);
+        let client = McpClient::new(r, w).set_timeout(Duration::from_millis(50));
+        let err = client.request("ping", json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("timeout"), "{}", err);
+        assert!(client.pending.lock().await.is_empty());
+
+        //连接断开后的请求立即失败，而不是一直等待
+        drop(server_side);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let err = client.request("ping", json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("connection closed"), "{}", err);
+        assert!(client.pending.lock().await.is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::core::{
+    Ctx, JsonSchemaService, JsonService, JsonServiceExt, Service, ServiceEntity, ServiceLoader,
+};
+use crate::service::ext::Obj;
+use crate::service::mcp::{McpClient, McpTool};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use wd_tools::{PFArc, PFErr};
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct McpToolOutput {
+    //所有text内容的拼接
+    pub text: String,
+    pub content: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+}
+
+//一个MCP tool对应的service，输入即tool的arguments
+pub struct McpToolService {
+    pub client: Arc<McpClient>,
+    pub tool: McpTool,
+}
+
+#[async_trait::async_trait]
+impl JsonServiceExt<Obj, McpToolOutput> for McpToolService {
+    async fn call(
+        &self,
+        _ctx: Ctx,
+        input: Obj,
+        se: ServiceEntity,
+    ) -> anyhow::Result<McpToolOutput> {
+        let result = self
+            .client
+            .call_tool(self.tool.name.as_str(), input.into())
+            .await?;
+        if result.is_error {
+            return anyhow::anyhow!(
+                "Node[{}] mcp tool[{}] error: {}",
+                se.node_name,
+                self.tool.name,
+                result.text()
+            )
+            .err();
+        }
+        Ok(McpToolOutput {
+            text: result.text(),
+            content: result.content,
+            structured_content: result.structured_content,
+        })
+    }
+}
+
+//将MCP server的tools作为service加载，可配合ServiceLoaderWrap::set_service_loader使用
+#[derive(Default)]
+pub struct McpServiceLoader {
+    pub tools: HashMap<String, Arc<dyn Service + Sync + 'static>>,
+}
+
+impl McpServiceLoader {
+    //通过tools/list发现所有tool，service名为 prefix + tool名
+    pub async fn discover(client: Arc<McpClient>, prefix: &str) -> anyhow::Result<Self> {
+        let mut tools = HashMap::new();
+        for tool in client.list_tools().await? {
+            let name = format!("{}{}", prefix, tool.name);
+            let schema = tool.input_schema.clone();
+            let service = McpToolService {
+                client: client.clone(),
+                tool,
+            };
+            let service: Arc<dyn Service + Sync + 'static> = match schema {
+                Value::Object(_) => {
+                    JsonService::new(JsonSchemaService::new(service).set_input_schema(schema)).arc()
+                }
+                _ => JsonService::new(service).arc(),
+            };
+            tools.insert(name, service);
+        }
+        Ok(Self { tools })
+    }
+    //启动stdio server子进程，握手并发现tools
+    pub async fn spawn<S: AsRef<str>>(
+        command: &str,
+        args: &[S],
+        prefix: &str,
+    ) -> anyhow::Result<Self> {
+        let client = McpClient::spawn(command, args).await?;
+        Self::discover(client.arc(), prefix).await
+    }
+}
+
+#[async_trait::async_trait]
+impl ServiceLoader for McpServiceLoader {
+    async fn load(&self, name: &str) -> Option<Arc<dyn Service + Sync + 'static>> {
+        self.tools.get(name).cloned()
+    }
+    fn names(&self) -> Vec<String> {
+        let mut list = self.tools.keys().cloned().collect::<Vec<_>>();
+        list.sort();
+        list
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::ServiceLoader;
+    use crate::core::{CtxSerdeExt, EngineRT, JsonInput};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::ServiceLoaderWrap;
+    use crate::service::mcp::{McpClient, McpServiceLoader};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    //最小的MCP server：分两页返回tools，add求和，fail返回isError
+    async fn mock_server(stream: tokio::io::DuplexStream) {
+        let (r, mut w) = tokio::io::split(stream);
+        let mut lines = BufReader::new(r).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let req: Value = serde_json::from_str(line.as_str()).unwrap();
+            let schema = json!({"type":"object","required":["a","b"],"properties":{"a":{"type":"integer"},"b":{"type":"integer"}}});
+            let result = match req["method"].as_str().unwrap_or("") {
+                "initialize" => {
+                    json!({"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"mock","version":"0.1"}})
+                }
+                "tools/list" if req["params"]["cursor"].is_null() => {
+                    json!({"tools":[{"name":"add","description":"a + b","inputSchema":schema}],"nextCursor":"2"})
+                }
+                "tools/list" => json!({"tools":[{"name":"fail","inputSchema":{"type":"object"}}]}),
+                "tools/call" if req["params"]["name"] == "add" => {
+                    let args = &req["params"]["arguments"];
+                    let sum = args["a"].as_i64().unwrap_or(0) + args["b"].as_i64().unwrap_or(0);
+                    json!({"content":[{"type":"text","text":sum.to_string()}]})
+                }
+                "tools/call" => json!({"content":[{"type":"text","text":"boom"}],"isError":true}),
+                _ => continue,
+            };
+            let resp = json!({"jsonrpc":"2.0","id":req["id"],"result":result});
+            w.write_all(format!("{}\n", resp).as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mcp_loader() {
+        let (client_side, server_side) = tokio::io::duplex(4096);
+        tokio::spawn(mock_server(server_side));
+        let (r, w) = tokio::io::split(client_side);
+        let client = Arc::new(McpClient::new(r, w));
+        let info = client.initialize().await.unwrap();
+        assert_eq!(info["serverInfo"]["name"], "mock");
+
+        let loader = McpServiceLoader::discover(client, "mcp_").await.unwrap();
+        assert_eq!(loader.names(), vec!["mcp_add", "mcp_fail"]);
+        let rt = EngineRT::default()
+            .set_service_loader(ServiceLoaderWrap::default().set_service_loader(loader))
+            .build();
+        let plan = |service: &str| {
+            Pipeline::default()
+                .step((service, JsonInput::default()))
+                .check()
+                .unwrap()
+        };
+
+        let res: Value = rt
+            .ctx(plan("mcp_add"))
+            .serde_run(json!({"a":1,"b":2}))
+            .await
+            .unwrap();
+        assert_eq!(res["text"], "3");
+        let err = rt
+            .ctx(plan("mcp_add"))
+            .serde_run::<_, Value>(json!({"a":"1"}))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("input.a: expected integer"),
+            "{}",
+            err
+        );
+        let err = rt
+            .ctx(plan("mcp_fail"))
+            .serde_run::<_, Value>(json!({}))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("mcp tool[fail] error: boom"),
+            "{}",
+            err
+        );
+    }
+
+    //用sh实现的stdio server，通过子进程启动
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_mcp_loader_spawn() {
+        let script = r#"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  [ -z "$id" ] && continue
+  case "$line" in
+    *'"method":"initialize"'*) result='{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"sh","version":"0.1"}}' ;;
+    *'"method":"tools/list"'*) result='{"tools":[{"name":"echo","inputSchema":{"type":"object"}}]}' ;;
+    *'"method":"tools/call"'*)
+      text=$(printf '%s' "$line" | sed -n 's/.*"text":"\([^"]*\)".*/\1/p')
+      result="{\"content\":[{\"type\":\"text\",\"text\":\"$text\"}]}" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"not found"}}\n' "$id"; continue ;;
+  esac
+  printf '{"jsonrpc":"2.0","id":%s,"result":%s}\n' "$id" "$result"
+done
+"#;
+        let loader = McpServiceLoader::spawn("sh", &["-c", script], "sh_")
+            .await
+            .unwrap();
+        assert_eq!(loader.names(), vec!["sh_echo"]);
+        let rt = EngineRT::default()
+            .set_service_loader(ServiceLoaderWrap::default().set_service_loader(loader))
+            .build();
+        let plan = Pipeline::default()
+            .step(("sh_echo", JsonInput::default()))
+            .check()
+            .unwrap();
+        let res: Value = rt
+            .ctx(plan)
+            .serde_run(json!({"text":"hello"}))
+            .await
+            .unwrap();
+        assert_eq!(res["text"], "hello");
+
+        let client = McpClient::spawn("sh", &["-c", script]).await.unwrap();
+        let err = client
+            .request("resources/list", json!({}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("-32601"), "{}", err);
+        client.close().await.unwrap();
+    }
+}
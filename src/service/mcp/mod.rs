@@ -0,0 +1,7 @@
+mod client;
+mod loader;
+mod server;
+
+pub use client::*;
+pub use loader::*;
+pub use server::*;
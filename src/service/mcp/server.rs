@@ -0,0 +1,286 @@
+use crate::core::{Engine, JsonInput};
+use crate::plan::pipeline::Pipeline;
+use crate::service::ext::ServiceLoaderWrap;
+use crate::service::mcp::{McpTool, MCP_PROTOCOL_VERSION};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+use wd_tools::PFErr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum McpTarget {
+    Service(String),
+    Plan(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct McpPublished {
+    pub tool: McpTool,
+    pub target: McpTarget,
+}
+
+//MCP服务端：将engine中的service和注册的plan发布为tool，每次调用在新的ctx中运行
+pub struct McpServer {
+    pub engine: Engine,
+    pub tools: Vec<McpPublished>,
+}
+
+impl McpServer {
+    pub fn new(engine: Engine) -> Self {
+        Self {
+            engine,
+            tools: vec![],
+        }
+    }
+    //schema取自service的input_schema
+    pub async fn publish_service<N: Into<String>, D: Into<String>>(
+        mut self,
+        name: N,
+        description: D,
+    ) -> anyhow::Result<Self> {
+        let name = name.into();
+        let service = match self.engine.load_service(name.as_str()).await {
+            Some(s) => s,
+            None => return anyhow::anyhow!("McpServer publish service[{}] not found", name).err(),
+        };
+        let input_schema = service
+            .input_schema()
+            .unwrap_or_else(|| json!({"type":"object"}));
+        self.tools.push(McpPublished {
+            tool: McpTool {
+                name: name.clone(),
+                description: description.into(),
+                input_schema,
+            },
+            target: McpTarget::Service(name),
+        });
+        Ok(self)
+    }
+    //schema为None时不约束输入
+    pub fn publish_plan<N: Into<String>, D: Into<String>>(
+        mut self,
+        name: N,
+        description: D,
+        input_schema: Option<Value>,
+    ) -> anyhow::Result<Self> {
+        let name = name.into();
+        if self.engine.load_plan(name.as_str()).is_none() {
+            return anyhow::anyhow!("McpServer publish plan[{}] not registered", name).err();
+        }
+        self.tools.push(McpPublished {
+            tool: McpTool {
+                name: name.clone(),
+                description: description.into(),
+                input_schema: input_schema.unwrap_or_else(|| json!({"type":"object"})),
+            },
+            target: McpTarget::Plan(name),
+        });
+        Ok(self)
+    }
+    //发布所有可枚举的service(内置的流程service除外)和所有注册的plan
+    pub async fn publish_all(mut self) -> anyhow::Result<Self> {
+        let builtin = ServiceLoaderWrap::default().map_loader.map;
+        for name in self.engine.service_names() {
+            if !builtin.contains_key(name.as_str()) {
+                self = self.publish_service(name, "").await?;
+            }
+        }
+        for name in self.engine.plan_names() {
+            self = self.publish_plan(name, "", None)?;
+        }
+        Ok(self)
+    }
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> anyhow::Result<Value> {
+        let target = match self.tools.iter().find(|x| x.tool.name == name) {
+            Some(s) => s.target.clone(),
+            None => return anyhow::anyhow!("tool[{}] not found", name).err(),
+        };
+        match target {
+            McpTarget::Service(s) => {
+                let plan = Pipeline::default()
+                    .step((s.as_str(), JsonInput::default()))
+                    .check()?;
+                self.engine.ctx(plan).run(arguments).await
+            }
+            McpTarget::Plan(p) => match self.engine.load_plan(p.as_str()) {
+                Some(plan) => self.engine.ctx(plan).run(arguments).await,
+                None => anyhow::anyhow!("plan[{}] not registered", p).err(),
+            },
+        }
+    }
+    async fn handle(&self, req: &Value) -> Result<Value, (i64, String)> {
+        let params = &req["params"];
+        match req["method"].as_str().unwrap_or_default() {
+            "initialize" => Ok(json!({
+                //只支持这一个协议版本，由客户端决定是否继续
+                "protocolVersion": MCP_PROTOCOL_VERSION,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "art", "version": env!("CARGO_PKG_VERSION")},
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => {
+                let tools = self.tools.iter().map(|x| &x.tool).collect::<Vec<_>>();
+                Ok(json!({ "tools": tools }))
+            }
+            "tools/call" => {
+                let name = params["name"].as_str().unwrap_or_default();
+                let args = match params.get("arguments") {
+                    Some(Value::Null) | None => json!({}),
+                    Some(v) => v.clone(),
+                };
+                //执行错误作为tool结果返回，协议错误才用error
+                let result = match self.call_tool(name, args).await {
+                    Ok(Value::String(s)) => json!({"content":[{"type":"text","text":s}]}),
+                    //structuredContent必须是对象
+                    Ok(v @ Value::Object(_)) => {
+                        json!({"content":[{"type":"text","text":v.to_string()}],"structuredContent":v})
+                    }
+                    Ok(v) => json!({"content":[{"type":"text","text":v.to_string()}]}),
+                    Err(e) => {
+                        json!({"content":[{"type":"text","text":e.to_string()}],"isError":true})
+                    }
+                };
+                Ok(result)
+            }
+            m => Err((-32601, format!("method[{}] not found", m))),
+        }
+    }
+    //每个请求单独执行，响应按完成顺序写回
+    pub async fn serve<R, W>(self, reader: R, writer: W) -> anyhow::Result<()>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let server = Arc::new(self);
+        let writer = Arc::new(Mutex::new(writer));
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let req = match serde_json::from_str::<Value>(line.as_str()) {
+                Ok(v) => v,
+                Err(e) => {
+                    let resp = json!({"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":e.to_string()}});
+                    Self::write(&writer, resp).await?;
+                    continue;
+                }
+            };
+            //通知不需要响应
+            if req.get("id").is_none() {
+                continue;
+            }
+            let server = server.clone();
+            let writer = writer.clone();
+            tokio::spawn(async move {
+                let resp = match server.handle(&req).await {
+                    Ok(result) => json!({"jsonrpc":"2.0","id":req["id"],"result":result}),
+                    Err((code, message)) => {
+                        json!({"jsonrpc":"2.0","id":req["id"],"error":{"code":code,"message":message}})
+                    }
+                };
+                if let Err(e) = Self::write(&writer, resp).await {
+                    wd_log::log_field("error", e).warn("mcp server write response failed");
+                }
+            });
+        }
+        Ok(())
+    }
+    pub async fn serve_stdio(self) -> anyhow::Result<()> {
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+    async fn write<W: AsyncWrite + Send + Unpin>(
+        writer: &Mutex<W>,
+        msg: Value,
+    ) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(&msg)?;
+        line.push(b'\n');
+        let mut w = writer.lock().await;
+        w.write_all(&line).await?;
+        w.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{EngineRT, JsonInput};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::ServiceLoaderWrap;
+    use crate::service::mcp::{McpClient, McpServer, MCP_PROTOCOL_VERSION};
+    use serde_json::json;
+
+    #[derive(Default, serde::Serialize, serde::Deserialize)]
+    #[serde(default)]
+    struct AddReq {
+        a: i64,
+        b: i64,
+    }
+
+    #[tokio::test]
+    async fn test_mcp_server() {
+        let double = Pipeline::default()
+            .step(("add", JsonInput::default()))
+            .step((
+                "add",
+                JsonInput::default()
+                    .add_transform_quote("a", "add")
+                    .add_transform_quote("b", "add"),
+            ))
+            .check()
+            .unwrap();
+        let rt = EngineRT::default()
+            .set_service_loader(
+                ServiceLoaderWrap::default().register_json_ext_service_with_schema(
+                    "add",
+                    |_ctx, req: AddReq, _se| async move { Ok(req.a + req.b) },
+                    None,
+                    None,
+                ),
+            )
+            .register_plan("add_twice", double)
+            .build();
+        let server = McpServer::new(rt).publish_all().await.unwrap();
+
+        let (client_side, server_side) = tokio::io::duplex(4096);
+        let (sr, sw) = tokio::io::split(server_side);
+        tokio::spawn(server.serve(sr, sw));
+        let (cr, cw) = tokio::io::split(client_side);
+        let client = McpClient::new(cr, cw);
+        let info = client.initialize().await.unwrap();
+        assert_eq!(info["serverInfo"]["name"], "art");
+        let info = client
+            .request("initialize", json!({"protocolVersion":"2099-01-01"}))
+            .await
+            .unwrap();
+        assert_eq!(info["protocolVersion"], MCP_PROTOCOL_VERSION);
+
+        let tools = client.list_tools().await.unwrap();
+        let names = tools.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["add", "add_twice"]);
+        assert_eq!(tools[0].input_schema["properties"]["a"]["type"], "integer");
+
+        let res = client.call_tool("add", json!({"a":1,"b":2})).await.unwrap();
+        assert_eq!((res.text(), res.is_error), ("3".to_string(), false));
+        let res = client
+            .call_tool("add_twice", json!({"a":1,"b":2}))
+            .await
+            .unwrap();
+        assert_eq!(
+            (res.text(), res.structured_content),
+            ("6".to_string(), None)
+        );
+        let res = client.call_tool("add", json!({"a":"x"})).await.unwrap();
+        assert!(
+            res.is_error && res.text().contains("input.a"),
+            "{}",
+            res.text()
+        );
+        let err = client
+            .request("resources/list", json!({}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("-32601"), "{}", err);
+    }
+}
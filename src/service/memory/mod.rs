@@ -0,0 +1,5 @@
+mod service;
+mod store;
+
+pub use service::*;
+pub use store::*;
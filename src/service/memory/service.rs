@@ -0,0 +1,172 @@
+use crate::core::{Ctx, EnvExt, JsonServiceExt, ServiceEntity};
+use crate::service::llm::ChatMessage;
+use crate::service::memory::{MemoryStore, MemoryWindow};
+use std::sync::Arc;
+use wd_tools::PFErr;
+
+fn load_store(ctx: &Ctx, node: &str) -> anyhow::Result<Arc<MemoryStore>> {
+    match ctx.get_env().resource_ext::<MemoryStore>() {
+        Some(s) => Ok(s),
+        None => anyhow::anyhow!("Node[{}] MemoryStore not found in env", node).err(),
+    }
+}
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MemoryLoadCfg {
+    pub session_id: String,
+    pub window: MemoryWindow,
+}
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MemoryLoadOutput {
+    pub session_id: String,
+    pub messages: Vec<ChatMessage>,
+    //session中的消息总数，大于messages时说明有消息被窗口截掉
+    pub total: usize,
+}
+
+#[derive(Default, Debug)]
+pub struct MemoryLoad {}
+
+#[async_trait::async_trait]
+impl JsonServiceExt<MemoryLoadCfg, MemoryLoadOutput> for MemoryLoad {
+    async fn call(
+        &self,
+        ctx: Ctx,
+        cfg: MemoryLoadCfg,
+        se: ServiceEntity,
+    ) -> anyhow::Result<MemoryLoadOutput> {
+        if cfg.session_id.is_empty() {
+            return anyhow::anyhow!("Node[{}] memory_load session_id is empty", se.node_name).err();
+        }
+        let store = load_store(&ctx, se.node_name.as_str())?;
+        let (messages, total) = store
+            .0
+            .load_window(cfg.session_id.as_str(), &cfg.window)
+            .await?;
+        Ok(MemoryLoadOutput {
+            messages,
+            session_id: cfg.session_id,
+            total,
+        })
+    }
+}
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MemorySaveCfg {
+    pub session_id: String,
+    pub messages: Vec<ChatMessage>,
+    pub summary: Option<String>,
+    //先清空session再写入
+    pub clear: bool,
+}
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MemorySaveOutput {
+    pub session_id: String,
+    pub saved: usize,
+}
+
+#[derive(Default, Debug)]
+pub struct MemorySave {}
+
+#[async_trait::async_trait]
+impl JsonServiceExt<MemorySaveCfg, MemorySaveOutput> for MemorySave {
+    async fn call(
+        &self,
+        ctx: Ctx,
+        cfg: MemorySaveCfg,
+        se: ServiceEntity,
+    ) -> anyhow::Result<MemorySaveOutput> {
+        if cfg.session_id.is_empty() {
+            return anyhow::anyhow!("Node[{}] memory_save session_id is empty", se.node_name).err();
+        }
+        let store = load_store(&ctx, se.node_name.as_str())?;
+        let session = cfg.session_id.as_str();
+        if cfg.clear {
+            store.0.clear(session).await?;
+        }
+        if let Some(s) = cfg.summary {
+            store.0.set_summary(session, s).await?;
+        }
+        let saved = cfg.messages.len();
+        if saved > 0 {
+            store.0.append(session, cfg.messages).await?;
+        }
+        Ok(MemorySaveOutput {
+            session_id: cfg.session_id,
+            saved,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{CabinetEnv, CtxSerdeExt, EngineRT, JsonInput};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::ServiceLoaderWrap;
+    use crate::service::memory::{InMemoryMemory, MemoryStore};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_memory_service() {
+        let env =
+            Arc::new(CabinetEnv::new().set_resource(MemoryStore::new(InMemoryMemory::default())));
+        let rt = EngineRT::default()
+            .set_service_loader(ServiceLoaderWrap::default())
+            .build();
+        //保存本轮对话，再取最近两条
+        let plan = Pipeline::default()
+            .step((
+                "memory_save",
+                JsonInput::default()
+                    .add_transform_quote("session_id", "start.session_id")
+                    .add_transform_quote("messages", "start.messages"),
+            ))
+            .step((
+                "memory_load",
+                JsonInput::default().set_default_json(json!({
+                    "session_id":"${{start.session_id}}",
+                    "window":{"type":"last_n","n":2},
+                })),
+            ))
+            .check()
+            .unwrap();
+        let turn = |q: &str, a: &str| json!({"session_id":"u1","messages":[{"role":"user","content":q},{"role":"assistant","content":a}]});
+
+        let res: Value = rt
+            .ctx(plan.clone())
+            .set_env(env.clone())
+            .serde_run(turn("hi", "hello"))
+            .await
+            .unwrap();
+        assert_eq!(res["total"], 2);
+        let res: Value = rt
+            .ctx(plan)
+            .set_env(env.clone())
+            .serde_run(turn("1+1?", "2"))
+            .await
+            .unwrap();
+        assert_eq!(res["total"], 4);
+        assert_eq!(
+            res["messages"],
+            json!([{"role":"user","content":"1+1?"},{"role":"assistant","content":"2"}])
+        );
+
+        let plan = Pipeline::default()
+            .step(("memory_load", JsonInput::default()))
+            .check()
+            .unwrap();
+        let err = rt
+            .ctx(plan)
+            .serde_run::<_, Value>(json!({"session_id":"u1"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("MemoryStore not found"), "{}", err);
+    }
+}
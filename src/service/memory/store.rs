@@ -0,0 +1,303 @@
+use crate::service::llm::ChatMessage;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use wd_tools::PFErr;
+
+//粗略估算：约4个字符一个token，每条消息额外4个token
+pub fn estimate_tokens(msg: &ChatMessage) -> usize {
+    msg.content.chars().count().div_ceil(4) + 4
+}
+
+#[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MemoryWindow {
+    #[default]
+    All,
+    LastN {
+        n: usize,
+    },
+    //从最近的消息往前取，总token数不超过max_tokens，至少保留最后一条
+    TokenBudget {
+        max_tokens: usize,
+    },
+    //摘要作为一条system消息，加上最近的recent条消息
+    SummaryRecent {
+        recent: usize,
+    },
+}
+
+impl MemoryWindow {
+    pub fn apply(
+        &self,
+        mut messages: Vec<ChatMessage>,
+        summary: Option<String>,
+    ) -> Vec<ChatMessage> {
+        let keep = match self {
+            MemoryWindow::All => messages.len(),
+            MemoryWindow::LastN { n } | MemoryWindow::SummaryRecent { recent: n } => *n,
+            MemoryWindow::TokenBudget { max_tokens } => {
+                let mut total = 0;
+                messages
+                    .iter()
+                    .rev()
+                    .take_while(|x| {
+                        total += estimate_tokens(x);
+                        total <= *max_tokens
+                    })
+                    .count()
+                    .max(1)
+            }
+        };
+        let mut list = messages.split_off(messages.len().saturating_sub(keep));
+        if let (MemoryWindow::SummaryRecent { .. }, Some(s)) = (self, summary) {
+            list.insert(
+                0,
+                ChatMessage::system(format!("Summary of the earlier conversation: {}", s)),
+            );
+        }
+        list
+    }
+}
+
+#[async_trait::async_trait]
+pub trait ConversationMemory: Send + Sync {
+    async fn append(&self, session: &str, messages: Vec<ChatMessage>) -> anyhow::Result<()>;
+    async fn load(&self, session: &str) -> anyhow::Result<Vec<ChatMessage>>;
+    async fn clear(&self, session: &str) -> anyhow::Result<()>;
+    async fn set_summary(&self, session: &str, summary: String) -> anyhow::Result<()>;
+    async fn summary(&self, session: &str) -> anyhow::Result<Option<String>>;
+
+    //返回窗口内的消息和session中的消息总数
+    async fn load_window(
+        &self,
+        session: &str,
+        window: &MemoryWindow,
+    ) -> anyhow::Result<(Vec<ChatMessage>, usize)> {
+        let messages = self.load(session).await?;
+        let total = messages.len();
+        let summary = match window {
+            MemoryWindow::SummaryRecent { .. } => self.summary(session).await?,
+            _ => None,
+        };
+        Ok((window.apply(messages, summary), total))
+    }
+}
+
+//通过CabinetEnv::set_resource(MemoryStore::new(..))放入env
+#[derive(Clone)]
+pub struct MemoryStore(pub Arc<dyn ConversationMemory>);
+
+impl MemoryStore {
+    pub fn new<M: ConversationMemory + 'static>(m: M) -> Self {
+        Self(Arc::new(m))
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+struct Session {
+    messages: Vec<ChatMessage>,
+    summary: Option<String>,
+}
+
+#[derive(Default)]
+pub struct InMemoryMemory {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+#[async_trait::async_trait]
+impl ConversationMemory for InMemoryMemory {
+    async fn append(&self, session: &str, mut messages: Vec<ChatMessage>) -> anyhow::Result<()> {
+        let mut lock = self.sessions.lock().await;
+        lock.entry(session.to_string())
+            .or_default()
+            .messages
+            .append(&mut messages);
+        Ok(())
+    }
+
+    async fn load(&self, session: &str) -> anyhow::Result<Vec<ChatMessage>> {
+        let lock = self.sessions.lock().await;
+        Ok(lock
+            .get(session)
+            .map(|x| x.messages.clone())
+            .unwrap_or_default())
+    }
+
+    async fn clear(&self, session: &str) -> anyhow::Result<()> {
+        self.sessions.lock().await.remove(session);
+        Ok(())
+    }
+
+    async fn set_summary(&self, session: &str, summary: String) -> anyhow::Result<()> {
+        let mut lock = self.sessions.lock().await;
+        lock.entry(session.to_string()).or_default().summary = Some(summary);
+        Ok(())
+    }
+
+    async fn summary(&self, session: &str) -> anyhow::Result<Option<String>> {
+        let lock = self.sessions.lock().await;
+        Ok(lock.get(session).and_then(|x| x.summary.clone()))
+    }
+}
+
+//每个session一个文件：{dir}/{session}.jsonl 保存消息，{dir}/{session}.summary 保存摘要
+pub struct FileMemory {
+    pub dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileMemory {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            lock: Mutex::new(()),
+        }
+    }
+    fn path(&self, session: &str, ext: &str) -> anyhow::Result<PathBuf> {
+        if session.is_empty()
+            || !session
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return anyhow::anyhow!("FileMemory invalid session id[{}]", session).err();
+        }
+        Ok(self.dir.join(format!("{}.{}", session, ext)))
+    }
+    async fn remove(path: PathBuf) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ConversationMemory for FileMemory {
+    async fn append(&self, session: &str, messages: Vec<ChatMessage>) -> anyhow::Result<()> {
+        let path = self.path(session, "jsonl")?;
+        let mut buf = vec![];
+        for m in messages.iter() {
+            buf.extend(serde_json::to_vec(m)?);
+            buf.push(b'\n');
+        }
+        let _lock = self.lock.lock().await;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(&buf).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn load(&self, session: &str) -> anyhow::Result<Vec<ChatMessage>> {
+        let path = self.path(session, "jsonl")?;
+        let _lock = self.lock.lock().await;
+        let text = match tokio::fs::read_to_string(path).await {
+            Ok(s) => s,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut list = vec![];
+        for line in text.lines().filter(|x| !x.trim().is_empty()) {
+            list.push(serde_json::from_str::<ChatMessage>(line)?);
+        }
+        Ok(list)
+    }
+
+    async fn clear(&self, session: &str) -> anyhow::Result<()> {
+        let (messages, summary) = (self.path(session, "jsonl")?, self.path(session, "summary")?);
+        let _lock = self.lock.lock().await;
+        Self::remove(messages).await?;
+        Self::remove(summary).await
+    }
+
+    async fn set_summary(&self, session: &str, summary: String) -> anyhow::Result<()> {
+        let path = self.path(session, "summary")?;
+        let _lock = self.lock.lock().await;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(path, summary).await?;
+        Ok(())
+    }
+
+    async fn summary(&self, session: &str) -> anyhow::Result<Option<String>> {
+        let path = self.path(session, "summary")?;
+        let _lock = self.lock.lock().await;
+        match tokio::fs::read_to_string(path).await {
+            Ok(s) => Ok(Some(s)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::service::llm::ChatMessage;
+    use crate::service::memory::{ConversationMemory, FileMemory, MemoryWindow};
+
+    #[tokio::test]
+    async fn test_file_memory() {
+        let dir = std::env::temp_dir().join(format!("art_memory_{}", std::process::id()));
+        let memory = FileMemory::new(dir.clone());
+        memory
+            .append(
+                "s1",
+                vec![
+                    ChatMessage::user("hello"),
+                    ChatMessage::assistant("hi, how can I help"),
+                ],
+            )
+            .await
+            .unwrap();
+        memory
+            .append("s1", vec![ChatMessage::user("weather?")])
+            .await
+            .unwrap();
+        assert_eq!(memory.load("s1").await.unwrap().len(), 3);
+        assert!(memory.load("s2").await.unwrap().is_empty());
+        assert!(memory.load("../x").await.is_err());
+
+        let (list, total) = memory
+            .load_window("s1", &MemoryWindow::LastN { n: 2 })
+            .await
+            .unwrap();
+        assert_eq!(list[0].content, "hi, how can I help");
+        assert_eq!(total, 3);
+        //hello:6 hi..:9 weather?:6
+        let (list, _) = memory
+            .load_window("s1", &MemoryWindow::TokenBudget { max_tokens: 15 })
+            .await
+            .unwrap();
+        assert_eq!(list.len(), 2);
+        //最后一条单独超出预算时仍然保留
+        let (list, _) = memory
+            .load_window("s1", &MemoryWindow::TokenBudget { max_tokens: 1 })
+            .await
+            .unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].content, "weather?");
+
+        memory
+            .set_summary("s1", "user greeted".into())
+            .await
+            .unwrap();
+        let (list, _) = memory
+            .load_window("s1", &MemoryWindow::SummaryRecent { recent: 1 })
+            .await
+            .unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].role, "system");
+        assert!(list[0].content.contains("user greeted"));
+
+        memory.clear("s1").await.unwrap();
+        assert!(memory.load("s1").await.unwrap().is_empty());
+        assert_eq!(memory.summary("s1").await.unwrap(), None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
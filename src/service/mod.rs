@@ -2,4 +2,7 @@ pub mod agent;
 pub mod custom;
 pub mod ext;
 pub mod flow;
-mod mcp;
+pub mod llm;
+pub mod mcp;
+pub mod memory;
+pub mod prompt;
@@ -0,0 +1,5 @@
+mod registry;
+mod service;
+
+pub use registry::*;
+pub use service::*;
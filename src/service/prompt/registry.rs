@@ -0,0 +1,214 @@
+use crate::utils::{expr, path, string};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use wd_tools::PFErr;
+
+#[derive(Default, Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    //模板中可以引用的变量(根字段)
+    pub variables: Vec<String>,
+    //${{ }}语法，与default_json一致
+    pub template: String,
+}
+
+impl PromptTemplate {
+    pub fn new<N: Into<String>, V: Into<String>, T: Into<String>>(
+        name: N,
+        version: V,
+        template: T,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            template: template.into(),
+            ..Default::default()
+        }
+    }
+    pub fn set_variables<S: Into<String>, I: IntoIterator<Item = S>>(mut self, vars: I) -> Self {
+        self.variables = vars.into_iter().map(|x| x.into()).collect();
+        self
+    }
+    pub fn set_description<S: Into<String>>(mut self, s: S) -> Self {
+        self.description = s.into();
+        self
+    }
+    pub fn key(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+    //模板引用到的变量
+    pub fn referenced(&self) -> BTreeSet<String> {
+        string::extract_template_content(self.template.as_str())
+            .iter()
+            .flat_map(|x| expr::template_paths(x))
+            .map(|x| path::split_root(x.as_str()).0.to_string())
+            .collect()
+    }
+    fn undeclared(&self) -> Vec<String> {
+        self.referenced()
+            .into_iter()
+            .filter(|x| !self.variables.contains(x))
+            .collect()
+    }
+    //vars中缺少声明的变量，或者模板引用了未声明的变量都会报错
+    pub fn render(&self, vars: &Value) -> anyhow::Result<String> {
+        let unknown = self.undeclared();
+        if !unknown.is_empty() {
+            return anyhow::anyhow!("prompt[{}] unknown variables {:?}", self.key(), unknown).err();
+        }
+        let missing = self
+            .variables
+            .iter()
+            .filter(|x| vars.get(x.as_str()).is_none())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return anyhow::anyhow!("prompt[{}] missing variables {:?}", self.key(), missing).err();
+        }
+        let lookup = |p: &str| path::query(vars, p).ok();
+        let mut errs = vec![];
+        let text = string::render_template(self.template.as_str(), |c| {
+            match expr::parse(c).and_then(|e| e.eval(&lookup)) {
+                Ok(v) => Some(expr::to_text(&v)),
+                Err(e) => {
+                    errs.push(format!("${{{{{}}}}}: {}", c, e));
+                    None
+                }
+            }
+        });
+        if !errs.is_empty() {
+            return anyhow::anyhow!("prompt[{}] render error: {}", self.key(), errs.join("; "))
+                .err();
+        }
+        Ok(text)
+    }
+}
+
+//按.分段比较，数字段按数值比较：1.10 > 1.9
+fn compare_version(a: &str, b: &str) -> Ordering {
+    let (sa, sb) = (
+        a.split('.').collect::<Vec<_>>(),
+        b.split('.').collect::<Vec<_>>(),
+    );
+    for (x, y) in sa.iter().zip(sb.iter()) {
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(i), Ok(j)) => i.cmp(&j),
+            _ => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    sa.len().cmp(&sb.len())
+}
+
+//通过CabinetEnv::set_resource放入env，供prompt_render使用
+#[derive(Default, Debug, Clone)]
+pub struct PromptRegistry {
+    pub templates: HashMap<String, Vec<PromptTemplate>>,
+}
+
+impl PromptRegistry {
+    pub fn register(mut self, tpl: PromptTemplate) -> anyhow::Result<Self> {
+        if tpl.name.is_empty() || tpl.version.is_empty() || tpl.name.contains('@') {
+            return anyhow::anyhow!("prompt name[{}] version[{}] invalid", tpl.name, tpl.version)
+                .err();
+        }
+        let unknown = tpl.undeclared();
+        if !unknown.is_empty() {
+            return anyhow::anyhow!(
+                "prompt[{}] references undeclared variables {:?}",
+                tpl.key(),
+                unknown
+            )
+            .err();
+        }
+        let list = self.templates.entry(tpl.name.clone()).or_default();
+        if list.iter().any(|x| x.version == tpl.version) {
+            return anyhow::anyhow!("prompt[{}] already registered", tpl.key()).err();
+        }
+        list.push(tpl);
+        list.sort_by(|a, b| compare_version(a.version.as_str(), b.version.as_str()));
+        Ok(self)
+    }
+    //name@version，省略版本时取最新版本
+    pub fn get(&self, key: &str) -> Option<&PromptTemplate> {
+        let (name, version) = match key.split_once('@') {
+            Some((n, v)) => (n, Some(v)),
+            None => (key, None),
+        };
+        let list = self.templates.get(name)?;
+        match version {
+            Some(v) => list.iter().find(|x| x.version == v),
+            None => list.last(),
+        }
+    }
+    pub fn versions(&self, name: &str) -> Vec<String> {
+        self.templates
+            .get(name)
+            .map(|x| x.iter().map(|t| t.version.clone()).collect())
+            .unwrap_or_default()
+    }
+    pub fn render(&self, key: &str, vars: &Value) -> anyhow::Result<String> {
+        match self.get(key) {
+            Some(t) => t.render(vars),
+            None => anyhow::anyhow!("prompt[{}] not found", key).err(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::service::prompt::{PromptRegistry, PromptTemplate};
+    use serde_json::json;
+
+    #[test]
+    fn test_prompt_registry() {
+        let registry = PromptRegistry::default()
+            .register(PromptTemplate::new("summary", "1.9", "Summarize: ${{text}}").set_variables(["text"]))
+            .unwrap()
+            .register(
+                PromptTemplate::new("summary", "1.10", "Summarize in ${{lang | default('English')}} for ${{user.name | upper}}:\n${{text}}")
+                    .set_variables(["text", "lang", "user"]),
+            )
+            .unwrap();
+        assert_eq!(registry.versions("summary"), vec!["1.9", "1.10"]);
+        assert_eq!(registry.get("summary").unwrap().version, "1.10");
+
+        let vars = json!({"text":"a long story","lang":null,"user":{"name":"bob"}});
+        assert_eq!(
+            registry.render("summary", &vars).unwrap(),
+            "Summarize in English for BOB:\na long story"
+        );
+        assert_eq!(
+            registry.render("summary@1.9", &vars).unwrap(),
+            "Summarize: a long story"
+        );
+
+        let err = registry
+            .render("summary", &json!({"text":"x"}))
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(r#"missing variables ["lang", "user"]"#),
+            "{}",
+            err
+        );
+        assert!(registry.render("summary@2", &vars).is_err());
+        let err = PromptRegistry::default()
+            .register(
+                PromptTemplate::new("qa", "1", "${{question}} ${{context}}")
+                    .set_variables(["question"]),
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(r#"undeclared variables ["context"]"#),
+            "{}",
+            err
+        );
+    }
+}
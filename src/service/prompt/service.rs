@@ -0,0 +1,115 @@
+use crate::core::{Ctx, EnvExt, JsonServiceExt, ServiceEntity};
+use crate::service::ext::Obj;
+use crate::service::prompt::PromptRegistry;
+use wd_tools::PFErr;
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PromptRenderCfg {
+    //name@version，省略版本时取最新版本
+    pub prompt: String,
+    pub variables: Obj,
+}
+
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PromptRenderOutput {
+    pub name: String,
+    pub version: String,
+    pub text: String,
+}
+
+#[derive(Default, Debug)]
+pub struct PromptRender {}
+
+#[async_trait::async_trait]
+impl JsonServiceExt<PromptRenderCfg, PromptRenderOutput> for PromptRender {
+    async fn call(
+        &self,
+        ctx: Ctx,
+        cfg: PromptRenderCfg,
+        se: ServiceEntity,
+    ) -> anyhow::Result<PromptRenderOutput> {
+        let registry = match ctx.get_env().resource_ext::<PromptRegistry>() {
+            Some(r) => r,
+            None => {
+                return anyhow::anyhow!("Node[{}] PromptRegistry not found in env", se.node_name)
+                    .err()
+            }
+        };
+        let tpl = match registry.get(cfg.prompt.as_str()) {
+            Some(t) => t,
+            None => {
+                return anyhow::anyhow!("Node[{}] prompt[{}] not found", se.node_name, cfg.prompt)
+                    .err()
+            }
+        };
+        let text = match tpl.render(&cfg.variables.into()) {
+            Ok(s) => s,
+            Err(e) => return anyhow::anyhow!("Node[{}] {}", se.node_name, e).err(),
+        };
+        Ok(PromptRenderOutput {
+            name: tpl.name.clone(),
+            version: tpl.version.clone(),
+            text,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::{CabinetEnv, CtxSerdeExt, EngineRT, JsonInput};
+    use crate::plan::pipeline::Pipeline;
+    use crate::service::ext::ServiceLoaderWrap;
+    use crate::service::prompt::{PromptRegistry, PromptTemplate};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_prompt_render() {
+        let registry = PromptRegistry::default()
+            .register(
+                PromptTemplate::new(
+                    "greet",
+                    "1",
+                    "Hello ${{name}}, you have ${{count + 1}} messages",
+                )
+                .set_variables(["name", "count"]),
+            )
+            .unwrap();
+        let env = Arc::new(CabinetEnv::new().set_resource(registry));
+        let rt = EngineRT::default()
+            .set_service_loader(ServiceLoaderWrap::default())
+            .build();
+        let plan = Pipeline::default()
+            .step((
+                "prompt_render",
+                JsonInput::default()
+                    .set_default_json(json!({"prompt": "greet@1"}))
+                    .add_transform_quote("variables", "start"),
+            ))
+            .check()
+            .unwrap();
+        let res: Value = rt
+            .ctx(plan.clone())
+            .set_env(env.clone())
+            .serde_run(json!({"name":"bob","count":2}))
+            .await
+            .unwrap();
+        assert_eq!(res["text"], "Hello bob, you have 3 messages");
+        assert_eq!(res["version"], "1");
+
+        let err = rt
+            .ctx(plan)
+            .set_env(env)
+            .serde_run::<_, Value>(json!({"name":"bob"}))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(r#"prompt[greet@1] missing variables ["count"]"#),
+            "{}",
+            err
+        );
+    }
+}
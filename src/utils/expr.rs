@@ -0,0 +1,638 @@
+use serde_json::{Map, Number, Value};
+use std::fmt::{Display, Formatter};
+
+// ${{ }} 中的表达式
+// 优先级由低到高: | 过滤器 > ?: > || > && > 比较 > + - > * / % > ! -
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Value(Value),
+    Path(String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(String, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    Filter(Box<Expr>, String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Value(Value),
+    Path(String),
+    Op(&'static str),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Value(v) => write!(f, "{}", v),
+            Token::Path(p) => write!(f, "{}", p),
+            Token::Op(o) => write!(f, "{}", o),
+        }
+    }
+}
+
+const OPS: [&str; 22] = [
+    "==", "!=", ">=", "<=", "&&", "||", ">", "<", "+", "-", "*", "/", "%", "!", "?", ":", "|", "(",
+    ")", ",", "[", "]",
+];
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn tokenize(s: &str) -> anyhow::Result<Vec<Token>> {
+    let cs = s.chars().collect::<Vec<_>>();
+    let mut list = vec![];
+    let mut i = 0;
+    'outer: while i < cs.len() {
+        let c = cs[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '\'' || c == '"' {
+            let mut buf = String::new();
+            i += 1;
+            while i < cs.len() && cs[i] != c {
+                if cs[i] == '\\' && i + 1 < cs.len() {
+                    i += 1;
+                    buf.push(match cs[i] {
+                        'n' => '\n',
+                        't' => '\t',
+                        x => x,
+                    });
+                } else {
+                    buf.push(cs[i]);
+                }
+                i += 1;
+            }
+            if i >= cs.len() {
+                return Err(anyhow::anyhow!("expr[{}] unterminated string", s));
+            }
+            i += 1;
+            list.push(Token::Value(Value::String(buf)));
+            continue;
+        }
+        if c.is_ascii_digit() {
+            let start = i;
+            while i < cs.len() && (cs[i].is_ascii_digit() || cs[i] == '.') {
+                i += 1;
+            }
+            let text = cs[start..i].iter().collect::<String>();
+            let val = serde_json::from_str::<Number>(text.as_str())
+                .map_err(|_| anyhow::anyhow!("expr[{}] invalid number[{}]", s, text))?;
+            list.push(Token::Value(Value::Number(val)));
+            continue;
+        }
+        if c.is_alphabetic() || c == '_' {
+            let start = i;
+            let mut depth = 0;
+            while i < cs.len() {
+                let x = cs[i];
+                if depth > 0 {
+                    if x == '[' {
+                        depth += 1;
+                    } else if x == ']' {
+                        depth -= 1;
+                    }
+                } else if x == '[' {
+                    depth += 1;
+                } else if (x == '-' || x == '#')
+                    && is_ident(cs[i - 1])
+                    && cs.get(i + 1).is_some_and(|c| is_ident(*c))
+                {
+                    //节点名中的连字符和子节点序号，如 tool-search.result、batch_add#2；减法需要用空格分隔
+                } else if !(x.is_alphanumeric()
+                    || x == '_'
+                    || x == '.'
+                    || (x == '*' && cs[i - 1] == '.'))
+                {
+                    break;
+                }
+                i += 1;
+            }
+            let text = cs[start..i].iter().collect::<String>();
+            list.push(match text.as_str() {
+                "true" => Token::Value(Value::Bool(true)),
+                "false" => Token::Value(Value::Bool(false)),
+                "null" => Token::Value(Value::Null),
+                _ => Token::Path(text),
+            });
+            continue;
+        }
+        for op in OPS {
+            let oc = op.chars().collect::<Vec<_>>();
+            if cs[i..].starts_with(&oc) {
+                list.push(Token::Op(op));
+                i += oc.len();
+                continue 'outer;
+            }
+        }
+        return Err(anyhow::anyhow!("expr[{}] unexpected char[{}]", s, c));
+    }
+    Ok(list)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(o)) => Some(o),
+            _ => None,
+        }
+    }
+    fn eat(&mut self, op: &str) -> bool {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+    fn expect(&mut self, op: &str) -> anyhow::Result<()> {
+        if self.eat(op) {
+            return Ok(());
+        }
+        match self.tokens.get(self.pos) {
+            Some(t) => Err(anyhow::anyhow!(
+                "expr[{}] expect[{}] found[{}]",
+                self.src,
+                op,
+                t
+            )),
+            None => Err(anyhow::anyhow!(
+                "expr[{}] expect[{}] found end",
+                self.src,
+                op
+            )),
+        }
+    }
+    fn pipe(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.ternary()?;
+        while self.eat("|") {
+            let name = match self.tokens.get(self.pos) {
+                Some(Token::Path(p)) if !p.contains(['.', '[']) => p.clone(),
+                _ => return Err(anyhow::anyhow!("expr[{}] expect filter name", self.src)),
+            };
+            self.pos += 1;
+            let mut args = vec![];
+            if self.eat("(") && !self.eat(")") {
+                loop {
+                    args.push(self.pipe()?);
+                    if self.eat(")") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            left = Expr::Filter(Box::new(left), name, args);
+        }
+        Ok(left)
+    }
+    fn ternary(&mut self) -> anyhow::Result<Expr> {
+        let cond = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let a = self.ternary()?;
+        self.expect(":")?;
+        let b = self.ternary()?;
+        Ok(Expr::Ternary(Box::new(cond), Box::new(a), Box::new(b)))
+    }
+    fn binary(&mut self, level: usize) -> anyhow::Result<Expr> {
+        const LEVELS: [&[&str]; 5] = [
+            &["||"],
+            &["&&"],
+            &["==", "!=", ">=", "<=", ">", "<"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        if level >= LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = self.peek_op().filter(|x| LEVELS[level].contains(x)) {
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op.to_string(), Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+    fn primary(&mut self) -> anyhow::Result<Expr> {
+        let token = match self.tokens.get(self.pos) {
+            Some(t) => t.clone(),
+            None => return Err(anyhow::anyhow!("expr[{}] unexpected end", self.src)),
+        };
+        self.pos += 1;
+        match token {
+            Token::Value(v) => Ok(Expr::Value(v)),
+            Token::Path(p) => Ok(Expr::Path(p)),
+            Token::Op("(") => {
+                let e = self.pipe()?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Token::Op("[") => {
+                let mut list = vec![];
+                if !self.eat("]") {
+                    loop {
+                        list.push(self.pipe()?);
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                //数组字面量用json过滤器的形式表达会很别扭，这里直接构造
+                Ok(Expr::Filter(
+                    Box::new(Expr::Value(Value::Null)),
+                    "array".into(),
+                    list,
+                ))
+            }
+            t => Err(anyhow::anyhow!(
+                "expr[{}] unexpected token[{}]",
+                self.src,
+                t
+            )),
+        }
+    }
+}
+
+pub fn parse(s: &str) -> anyhow::Result<Expr> {
+    let tokens = tokenize(s)?;
+    let mut p = Parser {
+        src: s,
+        tokens,
+        pos: 0,
+    };
+    let e = p.pipe()?;
+    if let Some(t) = p.tokens.get(p.pos) {
+        return Err(anyhow::anyhow!("expr[{}] unexpected token[{}]", s, t));
+    }
+    Ok(e)
+}
+
+// 模板内容中引用的路径，解析失败时把整个内容视为路径
+pub fn template_paths(s: &str) -> Vec<String> {
+    match parse(s) {
+        Ok(e) => e.paths(),
+        Err(_) => vec![s.to_string()],
+    }
+}
+
+pub fn truthy(v: &Value) -> bool {
+    match v {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().map(|x| x != 0.0).unwrap_or(false),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+// 字符串原样输出，其他类型输出json
+pub fn to_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        _ => v.to_string(),
+    }
+}
+
+fn number(f: f64) -> anyhow::Result<Value> {
+    if f.fract() == 0.0 && f.abs() < i64::MAX as f64 {
+        return Ok(Value::from(f as i64));
+    }
+    match Number::from_f64(f) {
+        Some(n) => Ok(Value::Number(n)),
+        None => Err(anyhow::anyhow!("expr number[{}] is not finite", f)),
+    }
+}
+
+fn as_f64(v: &Value, op: &str) -> anyhow::Result<f64> {
+    match v {
+        Value::Number(n) => Ok(n.as_f64().unwrap_or(0.0)),
+        _ => Err(anyhow::anyhow!(
+            "expr operator[{}] not support value[{}]",
+            op,
+            v
+        )),
+    }
+}
+
+fn compare(op: &str, a: &Value, b: &Value) -> anyhow::Result<bool> {
+    let ord = match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x
+            .as_f64()
+            .unwrap_or(0.0)
+            .partial_cmp(&y.as_f64().unwrap_or(0.0)),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "expr operator[{}] can not compare [{}] and [{}]",
+                op,
+                a,
+                b
+            ))
+        }
+    };
+    let ord = match ord {
+        Some(o) => o,
+        None => return Ok(false),
+    };
+    Ok(match op {
+        ">" => ord.is_gt(),
+        ">=" => ord.is_ge(),
+        "<" => ord.is_lt(),
+        _ => ord.is_le(),
+    })
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        _ => a == b,
+    }
+}
+
+impl Expr {
+    pub fn as_path(&self) -> Option<&str> {
+        match self {
+            Expr::Path(p) => Some(p.as_str()),
+            _ => None,
+        }
+    }
+    pub fn paths(&self) -> Vec<String> {
+        let mut list = vec![];
+        self.collect_paths(&mut list);
+        list
+    }
+    fn collect_paths(&self, list: &mut Vec<String>) {
+        match self {
+            Expr::Value(_) => {}
+            Expr::Path(p) => {
+                if !list.contains(p) {
+                    list.push(p.clone())
+                }
+            }
+            Expr::Not(e) | Expr::Neg(e) => e.collect_paths(list),
+            Expr::Binary(_, a, b) => {
+                a.collect_paths(list);
+                b.collect_paths(list);
+            }
+            Expr::Ternary(c, a, b) => {
+                c.collect_paths(list);
+                a.collect_paths(list);
+                b.collect_paths(list);
+            }
+            Expr::Filter(e, _, args) => {
+                e.collect_paths(list);
+                args.iter().for_each(|x| x.collect_paths(list));
+            }
+        }
+    }
+    // 路径不存在时取null，配合default过滤器使用
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<Value>) -> anyhow::Result<Value> {
+        match self {
+            Expr::Value(v) => Ok(v.clone()),
+            Expr::Path(p) => Ok(lookup(p.as_str()).unwrap_or(Value::Null)),
+            Expr::Not(e) => Ok(Value::Bool(!truthy(&e.eval(lookup)?))),
+            Expr::Neg(e) => {
+                let v = e.eval(lookup)?;
+                match v.as_i64().and_then(|i| i.checked_neg()) {
+                    Some(i) => Ok(Value::from(i)),
+                    None => number(-as_f64(&v, "-")?),
+                }
+            }
+            Expr::Ternary(c, a, b) => {
+                if truthy(&c.eval(lookup)?) {
+                    a.eval(lookup)
+                } else {
+                    b.eval(lookup)
+                }
+            }
+            Expr::Binary(op, a, b) => {
+                let a = a.eval(lookup)?;
+                //短路求值
+                match op.as_str() {
+                    "&&" if !truthy(&a) => return Ok(Value::Bool(false)),
+                    "||" if truthy(&a) => return Ok(Value::Bool(true)),
+                    _ => {}
+                }
+                let b = b.eval(lookup)?;
+                Self::binary(op.as_str(), a, b)
+            }
+            Expr::Filter(e, name, args) => {
+                let v = e.eval(lookup)?;
+                let args = args
+                    .iter()
+                    .map(|x| x.eval(lookup))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Self::filter(name.as_str(), v, args)
+            }
+        }
+    }
+    fn binary(op: &str, a: Value, b: Value) -> anyhow::Result<Value> {
+        match op {
+            "&&" | "||" => Ok(Value::Bool(truthy(&b))),
+            "==" => Ok(Value::Bool(equal(&a, &b))),
+            "!=" => Ok(Value::Bool(!equal(&a, &b))),
+            ">" | ">=" | "<" | "<=" => Ok(Value::Bool(compare(op, &a, &b)?)),
+            "+" => match (a, b) {
+                (Value::Array(mut x), Value::Array(y)) => {
+                    x.extend(y);
+                    Ok(Value::Array(x))
+                }
+                (a @ Value::String(_), b) | (a, b @ Value::String(_)) => {
+                    Ok(Value::String(to_text(&a) + to_text(&b).as_str()))
+                }
+                (a, b) => Self::arithmetic(op, a, b),
+            },
+            _ => Self::arithmetic(op, a, b),
+        }
+    }
+    fn arithmetic(op: &str, a: Value, b: Value) -> anyhow::Result<Value> {
+        if let (Some(x), Some(y)) = (a.as_i64(), b.as_i64()) {
+            let res = match op {
+                "+" => x.checked_add(y),
+                "-" => x.checked_sub(y),
+                "*" => x.checked_mul(y),
+                "/" | "%" if y == 0 => return Err(anyhow::anyhow!("expr division by zero")),
+                //i64::MIN / -1 溢出时按浮点数计算
+                "/" if x.checked_rem(y) == Some(0) => x.checked_div(y),
+                "%" => x.checked_rem(y),
+                _ => None,
+            };
+            if let Some(r) = res {
+                return Ok(Value::from(r));
+            }
+        }
+        let x = as_f64(&a, op)?;
+        let y = as_f64(&b, op)?;
+        match op {
+            "+" => number(x + y),
+            "-" => number(x - y),
+            "*" => number(x * y),
+            "/" => number(x / y),
+            "%" => number(x % y),
+            _ => Err(anyhow::anyhow!("expr unknown operator[{}]", op)),
+        }
+    }
+    fn filter(name: &str, v: Value, args: Vec<Value>) -> anyhow::Result<Value> {
+        let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Null);
+        let res = match name {
+            "array" => Value::Array(args),
+            "upper" => Value::String(to_text(&v).to_uppercase()),
+            "lower" => Value::String(to_text(&v).to_lowercase()),
+            "trim" => Value::String(to_text(&v).trim().to_string()),
+            "string" => Value::String(to_text(&v)),
+            "json" => Value::String(v.to_string()),
+            "default" => {
+                if v.is_null() {
+                    arg(0)
+                } else {
+                    v
+                }
+            }
+            "length" => match &v {
+                Value::Null => Value::from(0),
+                Value::String(s) => Value::from(s.chars().count()),
+                Value::Array(a) => Value::from(a.len()),
+                Value::Object(o) => Value::from(o.len()),
+                _ => return Err(anyhow::anyhow!("expr filter[length] not support[{}]", v)),
+            },
+            "join" => match v {
+                Value::Array(a) => {
+                    let sep = to_text(&arg(0));
+                    let sep = if args.is_empty() { "," } else { sep.as_str() };
+                    Value::String(a.iter().map(to_text).collect::<Vec<_>>().join(sep))
+                }
+                _ => return Err(anyhow::anyhow!("expr filter[join] not support[{}]", v)),
+            },
+            "split" => {
+                let sep = to_text(&arg(0));
+                Value::Array(
+                    to_text(&v)
+                        .split(sep.as_str())
+                        .map(|x| Value::String(x.to_string()))
+                        .collect(),
+                )
+            }
+            "replace" => Value::String(
+                to_text(&v).replace(to_text(&arg(0)).as_str(), to_text(&arg(1)).as_str()),
+            ),
+            "first" => match v {
+                Value::Array(a) => a.into_iter().next().unwrap_or(Value::Null),
+                _ => return Err(anyhow::anyhow!("expr filter[first] not support[{}]", v)),
+            },
+            "last" => match v {
+                Value::Array(a) => a.into_iter().last().unwrap_or(Value::Null),
+                _ => return Err(anyhow::anyhow!("expr filter[last] not support[{}]", v)),
+            },
+            "keys" => match v {
+                Value::Object(o) => {
+                    Value::Array(o.keys().map(|x| Value::String(x.clone())).collect())
+                }
+                _ => return Err(anyhow::anyhow!("expr filter[keys] not support[{}]", v)),
+            },
+            "number" => match &v {
+                Value::Number(_) => v,
+                Value::String(s) => match s.trim().parse::<f64>() {
+                    Ok(f) => number(f)?,
+                    Err(_) => {
+                        return Err(anyhow::anyhow!("expr filter[number] can not parse[{}]", s))
+                    }
+                },
+                Value::Bool(b) => Value::from(*b as i64),
+                _ => return Err(anyhow::anyhow!("expr filter[number] not support[{}]", v)),
+            },
+            "object" => {
+                let mut map = Map::new();
+                for pair in args.chunks(2) {
+                    map.insert(
+                        to_text(&pair[0]),
+                        pair.get(1).cloned().unwrap_or(Value::Null),
+                    );
+                }
+                Value::Object(map)
+            }
+            _ => return Err(anyhow::anyhow!("expr unknown filter[{}]", name)),
+        };
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_expr() {
+        let vars = json!({
+            "start": {"name": "art", "score": 8, "tags": ["a", "b"], "rate": 0.5},
+        });
+        let lookup = |p: &str| {
+            let mut v = &vars;
+            for k in p.split('.') {
+                v = v.get(k)?;
+            }
+            Some(v.clone())
+        };
+        let eval = |s: &str| parse(s).unwrap().eval(&lookup).unwrap();
+
+        assert_eq!(eval("start.score + 2 * 3"), json!(14));
+        assert_eq!(eval("(start.score + 2) / 4"), json!(2.5));
+        assert_eq!(eval("start.score % 3 == 2"), json!(true));
+        assert_eq!(eval("start.rate * 2"), json!(1));
+        assert_eq!(eval("-start.score >= -8 && !false"), json!(true));
+        assert_eq!(eval("'hello ' + start.name"), json!("hello art"));
+        assert_eq!(eval("start.score > 9 ? 'high' : 'low'"), json!("low"));
+        assert_eq!(eval("start.name | upper"), json!("ART"));
+        assert_eq!(eval("start.missing | default('x')"), json!("x"));
+        assert_eq!(eval("start.tags | join(',')"), json!("a,b"));
+        assert_eq!(eval("start.tags | length"), json!(2));
+        assert_eq!(eval("start.tags | json"), json!(r#"["a","b"]"#));
+        assert_eq!(eval("start.name + '!' | upper | length"), json!(4));
+        assert_eq!(eval("[1, start.score]"), json!([1, 8]));
+        assert_eq!(parse("start.name").unwrap().as_path(), Some("start.name"));
+        assert_eq!(
+            parse("tool-search.result").unwrap().as_path(),
+            Some("tool-search.result")
+        );
+        assert_eq!(
+            parse("batch_add#2.result").unwrap().as_path(),
+            Some("batch_add#2.result")
+        );
+        assert_eq!(eval("start.score - 2"), json!(6));
+        assert_eq!(
+            parse("a.x > b.y ? a.x : c").unwrap().paths(),
+            vec!["a.x", "b.y", "c"]
+        );
+        assert_eq!(
+            eval("-(0 - 9223372036854775807 - 1)"),
+            json!(9223372036854775808.0)
+        );
+        assert_eq!(
+            eval("(0 - 9223372036854775807 - 1) / -1"),
+            json!(9223372036854775808.0)
+        );
+        assert_eq!(eval("(0 - 9223372036854775807 - 1) % -1"), json!(0));
+        assert!(parse("start.score +").is_err());
+        assert!(parse("1 | nope").unwrap().eval(&lookup).is_err());
+    }
+}
@@ -1 +1,3 @@
+pub mod expr;
+pub mod path;
 pub mod string;
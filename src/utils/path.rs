@@ -0,0 +1,379 @@
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+
+// 引用路径：a.b.0  a.b[0]  a[-1]  a[*]  a.*  a[1:3]  a[::2]  a..title  a["x.y"]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    // 递归查找所有后代中的key，None表示所有后代
+    Recursive(Option<String>),
+}
+
+impl Display for Segment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let opt = |x: &Option<i64>| x.map(|i| i.to_string()).unwrap_or_default();
+        match self {
+            Segment::Key(k) => write!(f, ".{}", k),
+            Segment::Index(i) => write!(f, "[{}]", i),
+            Segment::Wildcard => write!(f, "[*]"),
+            Segment::Slice(a, b, None) => write!(f, "[{}:{}]", opt(a), opt(b)),
+            Segment::Slice(a, b, c) => write!(f, "[{}:{}:{}]", opt(a), opt(b), opt(c)),
+            Segment::Recursive(Some(k)) => write!(f, "..{}", k),
+            Segment::Recursive(None) => write!(f, "..*"),
+        }
+    }
+}
+
+fn parse_bracket(path: &str, s: &str) -> anyhow::Result<Segment> {
+    let s = s.trim();
+    if s == "*" {
+        return Ok(Segment::Wildcard);
+    }
+    if s.len() >= 2 && (s.starts_with('\'') || s.starts_with('"')) && s.ends_with(&s[..1]) {
+        return Ok(Segment::Key(s[1..s.len() - 1].to_string()));
+    }
+    let int = |x: &str| -> anyhow::Result<Option<i64>> {
+        let x = x.trim();
+        if x.is_empty() {
+            return Ok(None);
+        }
+        match x.parse::<i64>() {
+            Ok(i) => Ok(Some(i)),
+            Err(_) => Err(anyhow::anyhow!("path[{}] invalid index[{}]", path, x)),
+        }
+    };
+    if s.contains(':') {
+        let ss = s.split(':').collect::<Vec<_>>();
+        if ss.len() > 3 {
+            return Err(anyhow::anyhow!("path[{}] invalid slice[{}]", path, s));
+        }
+        let step = match ss.get(2) {
+            Some(x) => int(x)?,
+            None => None,
+        };
+        if step == Some(0) {
+            return Err(anyhow::anyhow!("path[{}] slice step can not be 0", path));
+        }
+        return Ok(Segment::Slice(int(ss[0])?, int(ss[1])?, step));
+    }
+    match int(s)? {
+        Some(i) => Ok(Segment::Index(i)),
+        None => Err(anyhow::anyhow!("path[{}] empty []", path)),
+    }
+}
+
+pub fn parse(path: &str) -> anyhow::Result<Vec<Segment>> {
+    let cs = path.chars().collect::<Vec<_>>();
+    let mut list = vec![];
+    let mut i = 0;
+    let name = |i: &mut usize| {
+        let start = *i;
+        while *i < cs.len() && cs[*i] != '.' && cs[*i] != '[' {
+            *i += 1;
+        }
+        cs[start..*i].iter().collect::<String>()
+    };
+    while i < cs.len() {
+        match cs[i] {
+            '.' if i + 1 < cs.len() && cs[i + 1] == '.' => {
+                i += 2;
+                let key = name(&mut i);
+                if key.is_empty() {
+                    return Err(anyhow::anyhow!("path[{}] expect key after ..", path));
+                }
+                list.push(Segment::Recursive(if key == "*" {
+                    None
+                } else {
+                    Some(key)
+                }));
+            }
+            '.' => {
+                i += 1;
+                let key = name(&mut i);
+                if key.is_empty() {
+                    return Err(anyhow::anyhow!("path[{}] empty field at {}", path, i));
+                }
+                list.push(if key == "*" {
+                    Segment::Wildcard
+                } else {
+                    Segment::Key(key)
+                });
+            }
+            '[' => {
+                let start = i + 1;
+                let mut quote = None;
+                i += 1;
+                while i < cs.len() {
+                    match (quote, cs[i]) {
+                        (None, '\'' | '"') => quote = Some(cs[i]),
+                        (Some(q), c) if q == c => quote = None,
+                        (None, ']') => break,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                if i >= cs.len() {
+                    return Err(anyhow::anyhow!("path[{}] unclosed [", path));
+                }
+                let inner = cs[start..i].iter().collect::<String>();
+                list.push(parse_bracket(path, inner.as_str())?);
+                i += 1;
+            }
+            _ => {
+                if !list.is_empty() {
+                    return Err(anyhow::anyhow!("path[{}] unexpected char at {}", path, i));
+                }
+                let key = name(&mut i);
+                list.push(if key == "*" {
+                    Segment::Wildcard
+                } else {
+                    Segment::Key(key)
+                });
+            }
+        }
+    }
+    Ok(list)
+}
+
+// 只包含普通字段和非负下标的路径，可以直接按.拆分处理
+pub fn is_plain(path: &str) -> bool {
+    match parse(path) {
+        Ok(list) => list
+            .iter()
+            .all(|x| matches!(x, Segment::Key(k) if !k.starts_with('-') && !k.contains('.'))),
+        Err(_) => false,
+    }
+}
+
+// 拆出路径的第一段(一般是节点名)：search.list[0] -> (search, list[0])
+// 节点名也可以用引号括起来：["batch_add#2"].result -> (batch_add#2, result)
+pub fn split_root(path: &str) -> (&str, &str) {
+    for q in ["[\"", "['"] {
+        let end = &q[1..];
+        if let Some(rest) = path.strip_prefix(q) {
+            if let Some(i) = rest.find(end) {
+                if rest[i + 1..].starts_with(']') {
+                    let field = &rest[i + 2..];
+                    return (&rest[..i], field.strip_prefix('.').unwrap_or(field));
+                }
+            }
+        }
+    }
+    match path.find(['.', '[']) {
+        Some(i) if path[i..].starts_with('.') => (&path[..i], &path[i + 1..]),
+        Some(i) => (&path[..i], &path[i..]),
+        None => (path, ""),
+    }
+}
+
+fn index(len: usize, i: i64) -> Option<usize> {
+    let i = if i < 0 {
+        (len as i64).saturating_add(i)
+    } else {
+        i
+    };
+    if i < 0 || i >= len as i64 {
+        None
+    } else {
+        Some(i as usize)
+    }
+}
+
+fn slice(len: usize, start: Option<i64>, end: Option<i64>, step: Option<i64>) -> Vec<usize> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    let clamp = |x: i64, lo: i64, hi: i64| {
+        let x = if x < 0 { x.saturating_add(len) } else { x };
+        x.clamp(lo, hi)
+    };
+    let mut list = vec![];
+    if step > 0 {
+        let mut i = start.map(|x| clamp(x, 0, len)).unwrap_or(0);
+        let end = end.map(|x| clamp(x, 0, len)).unwrap_or(len);
+        while i < end {
+            list.push(i as usize);
+            //步长过大时直接结束
+            i = match i.checked_add(step) {
+                Some(x) => x,
+                None => break,
+            };
+        }
+    } else {
+        let mut i = start.map(|x| clamp(x, -1, len - 1)).unwrap_or(len - 1);
+        let end = end.map(|x| clamp(x, -1, len - 1)).unwrap_or(-1);
+        while i > end {
+            list.push(i as usize);
+            //步长过大时直接结束
+            i = match i.checked_add(step) {
+                Some(x) => x,
+                None => break,
+            };
+        }
+    }
+    list
+}
+
+fn descendants<'a>(v: &'a Value, key: Option<&str>, out: &mut Vec<&'a Value>) {
+    match v {
+        Value::Object(map) => {
+            for (k, x) in map {
+                if key.map(|s| s == k).unwrap_or(true) {
+                    out.push(x);
+                }
+                descendants(x, key, out);
+            }
+        }
+        Value::Array(list) => {
+            for x in list {
+                if key.is_none() {
+                    out.push(x);
+                }
+                descendants(x, key, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// 查询路径，包含[*]、切片或..时结果为数组
+pub fn query(val: &Value, path: &str) -> anyhow::Result<Value> {
+    if path.is_empty() || path == "*" {
+        return Ok(val.clone());
+    }
+    let segments = parse(path)?;
+    let mut current = vec![val];
+    let mut projected = false;
+    let mut walked = String::new();
+    for seg in segments.iter() {
+        let mut next = vec![];
+        for v in current {
+            let found = match (seg, v) {
+                (Segment::Key(k), Value::Object(map)) => map.get(k.as_str()),
+                (Segment::Key(k), Value::Array(list)) => match k.parse::<i64>() {
+                    Ok(i) => index(list.len(), i).map(|i| &list[i]),
+                    Err(_) => None,
+                },
+                (Segment::Index(i), Value::Array(list)) => index(list.len(), *i).map(|i| &list[i]),
+                (Segment::Wildcard, Value::Object(map)) => {
+                    next.extend(map.values());
+                    continue;
+                }
+                (Segment::Wildcard, Value::Array(list)) => {
+                    next.extend(list.iter());
+                    continue;
+                }
+                (Segment::Slice(a, b, c), Value::Array(list)) => {
+                    next.extend(slice(list.len(), *a, *b, *c).into_iter().map(|i| &list[i]));
+                    continue;
+                }
+                (Segment::Recursive(k), _) => {
+                    descendants(v, k.as_deref(), &mut next);
+                    continue;
+                }
+                _ => None,
+            };
+            match found {
+                Some(x) => next.push(x),
+                None if projected => {}
+                None => {
+                    let at = if walked.is_empty() {
+                        "$"
+                    } else {
+                        walked.trim_start_matches('.')
+                    };
+                    let reason = match (seg, v) {
+                        (Segment::Key(k), Value::Object(_)) => format!("field[{}] not found", k),
+                        (Segment::Index(i), Value::Array(list)) => {
+                            format!("index[{}] out of range, len[{}]", i, list.len())
+                        }
+                        (Segment::Key(k), Value::Array(list)) => {
+                            format!("index[{}] invalid or out of range, len[{}]", k, list.len())
+                        }
+                        _ => format!("can not apply [{}] to {}", seg, type_name(v)),
+                    };
+                    return Err(anyhow::anyhow!("path[{}] {} at [{}]", path, reason, at));
+                }
+            }
+        }
+        if !matches!(seg, Segment::Key(_) | Segment::Index(_)) {
+            projected = true;
+        }
+        walked.push_str(seg.to_string().as_str());
+        current = next;
+    }
+    if projected {
+        return Ok(Value::Array(current.into_iter().cloned().collect()));
+    }
+    match current.pop() {
+        Some(v) => Ok(v.clone()),
+        None => Ok(Value::Null),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_path_query() {
+        let val = json!({
+            "results": [
+                {"title": "a", "url": "u1", "meta": {"title": "m1"}},
+                {"title": "b", "url": "u2"},
+                {"title": "c", "url": "u3"},
+            ],
+            "x.y": 1,
+        });
+        let q = |p: &str| query(&val, p).unwrap();
+        assert_eq!(q("results.0.title"), json!("a"));
+        assert_eq!(q("results[1].url"), json!("u2"));
+        assert_eq!(q("results[-1].title"), json!("c"));
+        assert_eq!(q("results[*].url"), json!(["u1", "u2", "u3"]));
+        assert_eq!(q("results.*.title"), json!(["a", "b", "c"]));
+        assert_eq!(q("results[1:].title"), json!(["b", "c"]));
+        assert_eq!(q("results[::-2].url"), json!(["u3", "u1"]));
+        assert_eq!(q("results..title"), json!(["m1", "a", "b", "c"]));
+        assert_eq!(q("['x.y']"), json!(1));
+        assert_eq!(q("results[1::9223372036854775807].title"), json!(["b"]));
+        assert_eq!(q("results[::-9223372036854775808].title"), json!(["c"]));
+        assert_eq!(
+            q("results[-9223372036854775808:].title"),
+            json!(["a", "b", "c"])
+        );
+        assert!(query(&val, "results[-9223372036854775808]").is_err());
+        assert_eq!(q("results[*].meta.title"), json!(["m1"]));
+
+        let err = query(&val, "results[5].title").unwrap_err().to_string();
+        assert!(err.contains("index[5] out of range"), "{}", err);
+        let err = query(&val, "results.0.name").unwrap_err().to_string();
+        assert!(
+            err.contains("field[name] not found at [results.0]"),
+            "{}",
+            err
+        );
+        assert!(query(&val, "results[a]").is_err());
+
+        assert_eq!(split_root("search.results[0]"), ("search", "results[0]"));
+        assert_eq!(split_root("search[0]"), ("search", "[0]"));
+        assert_eq!(split_root(r#"["batch#2"].result"#), ("batch#2", "result"));
+        assert_eq!(split_root("['batch#2'][0]"), ("batch#2", "[0]"));
+        assert_eq!(split_root("batch#2.result"), ("batch#2", "result"));
+        assert!(is_plain("a.b.c"));
+        assert!(!is_plain("a[0]"));
+    }
+}
@@ -1,12 +1,156 @@
-use regex::Regex;
+use crate::utils::expr;
+use serde_json::Value;
+
+enum Piece<'a> {
+    Text(&'a str),
+    Template(&'a str, &'a str),
+}
+
+//\${{ 转义为字面量 ${{，$${{ 中的首个$仍是普通字符
+fn scan_template(s: &str) -> Vec<Piece<'_>> {
+    let mut list = vec![];
+    let mut rest = s;
+    while let Some(i) = rest.find("${{") {
+        if rest[..i].ends_with('\\') {
+            list.push(Piece::Text(&rest[..i - 1]));
+            list.push(Piece::Text("${{"));
+            rest = &rest[i + 3..];
+            continue;
+        }
+        let end = match rest[i + 3..].find("}}") {
+            Some(e) => i + 3 + e + 2,
+            None => break,
+        };
+        list.push(Piece::Text(&rest[..i]));
+        list.push(Piece::Template(&rest[i..end], rest[i + 3..end - 2].trim()));
+        rest = &rest[end..];
+    }
+    list.push(Piece::Text(rest));
+    list.retain(|x| !matches!(x, Piece::Text("")));
+    list
+}
 
 pub fn extract_template_content(s: &str) -> Vec<String> {
-    let re = Regex::new(r"\$\{\{\s*(.*?)\s*\}\}").unwrap();
-    re.captures_iter(s)
-        .filter_map(|cap| cap.get(1).map(|m| m.as_str().to_string()))
+    scan_template(s)
+        .into_iter()
+        .filter_map(|x| match x {
+            Piece::Template(_, c) => Some(c.to_string()),
+            _ => None,
+        })
         .collect()
 }
 
+pub fn has_escaped_template(s: &str) -> bool {
+    s.contains("\\${{")
+}
+
+//整个字符串只有一个模板时返回模板内容
+pub fn whole_template(s: &str) -> Option<String> {
+    match scan_template(s).as_slice() {
+        [Piece::Template(_, c)] => Some(c.to_string()),
+        _ => None,
+    }
+}
+
+//替换s中的${{xxx}}，f返回None时保留原模板，同时还原转义的\${{
+pub fn render_template(s: &str, mut f: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(s.len());
+    for i in scan_template(s) {
+        match i {
+            Piece::Text(t) => out.push_str(t),
+            Piece::Template(raw, c) => match f(c) {
+                Some(v) => out.push_str(v.as_str()),
+                None => out.push_str(raw),
+            },
+        }
+    }
+    out
+}
+
+//字符串原样输出，其他类型按yaml风格输出，用于拼接prompt
+pub fn to_yaml(val: &Value) -> String {
+    let mut out = String::new();
+    yaml_value(val, 0, &mut out);
+    out.trim_end().to_string()
+}
+
+fn yaml_scalar(val: &Value) -> String {
+    match val {
+        Value::String(s) => {
+            let special = s.is_empty()
+                || s.trim() != s
+                || s.contains(": ")
+                || s.contains(" #")
+                || s.starts_with([
+                    '-', '[', '{', '#', '&', '*', '!', '|', '>', '"', '\'', '%', '@',
+                ]);
+            if special || s.contains('\n') {
+                Value::String(s.clone()).to_string()
+            } else {
+                s.clone()
+            }
+        }
+        Value::Array(_) => "[]".into(),
+        Value::Object(_) => "{}".into(),
+        _ => val.to_string(),
+    }
+}
+
+fn yaml_nested(val: &Value) -> bool {
+    match val {
+        Value::Array(l) => !l.is_empty(),
+        Value::Object(m) => !m.is_empty(),
+        _ => false,
+    }
+}
+
+fn yaml_value(val: &Value, indent: usize, out: &mut String) {
+    let pad = "  ".repeat(indent);
+    let item = |prefix: String, x: &Value, out: &mut String| {
+        if yaml_nested(x) {
+            out.push_str(format!("{}{}\n", pad, prefix).as_str());
+            yaml_value(x, indent + 1, out);
+        } else {
+            out.push_str(format!("{}{} {}\n", pad, prefix, yaml_scalar(x)).as_str());
+        }
+    };
+    match val {
+        Value::Object(m) if !m.is_empty() => {
+            m.iter().for_each(|(k, x)| item(format!("{}:", k), x, out))
+        }
+        Value::Array(l) if !l.is_empty() => l.iter().for_each(|x| item("-".into(), x, out)),
+        _ => out.push_str(format!("{}{}\n", pad, yaml_scalar(val)).as_str()),
+    }
+}
+
+//模板内容按表达式求值，无法解析时视为路径
+pub fn eval_template(content: &str, lookup: &dyn Fn(&str) -> Option<Value>) -> Option<Value> {
+    match expr::parse(content) {
+        Ok(e) if e.as_path().is_none() => e.eval(lookup).ok(),
+        _ => lookup(content),
+    }
+}
+
+//同步替换value中的${{xxx}}：整段模板替换为原类型，内嵌模板替换为文本
+pub fn render_template_value(val: &mut Value, lookup: &dyn Fn(&str) -> Option<Value>) {
+    match val {
+        Value::String(s) => {
+            if let Some(content) = whole_template(s) {
+                *val = eval_template(content.as_str(), lookup).unwrap_or(Value::Null);
+                return;
+            }
+            *s = render_template(s, |c| eval_template(c, lookup).map(|v| expr::to_text(&v)));
+        }
+        Value::Array(list) => list
+            .iter_mut()
+            .for_each(|v| render_template_value(v, lookup)),
+        Value::Object(obj) => obj
+            .values_mut()
+            .for_each(|v| render_template_value(v, lookup)),
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -21,5 +165,34 @@ mod tests {
         let res = extract_template_content(r#"f${{hello}}da&*(h${{world}}430&)"#);
         assert_eq!(res[0], "hello");
         assert_eq!(res[1], "world");
+        let res = extract_template_content(r#"\${{hello}} ${{ world }}"#);
+        assert_eq!(res, vec!["world"]);
+
+        let s = render_template(r"a ${{x}} \${{y}} ${{z}} $${{x}}", |c| {
+            if c == "x" {
+                Some("1".into())
+            } else {
+                None
+            }
+        });
+        assert_eq!(s, "a 1 ${{y}} ${{z}} $1");
+        assert_eq!(whole_template(" ${{x}}"), None);
+        assert_eq!(whole_template("${{ x }}"), Some("x".into()));
+
+        let val = serde_json::json!({"name":"art","tags":["a",{"k":"v: 1"}],"n":1});
+        assert_eq!(
+            to_yaml(&val),
+            "n: 1\nname: art\ntags:\n  - a\n  -\n    k: \"v: 1\""
+        );
+
+        let lookup = |c: &str| match c {
+            "name" => Some(serde_json::json!("art")),
+            "n" => Some(serde_json::json!(2)),
+            "tags" => Some(serde_json::json!(["a"])),
+            _ => None,
+        };
+        let mut val = serde_json::json!({"s":"hi ${{name}}, ${{n}} ${{tags}}","w":"${{n}}"});
+        render_template_value(&mut val, &lookup);
+        assert_eq!(val, serde_json::json!({"s":"hi art, 2 [\"a\"]","w":2}));
     }
 }